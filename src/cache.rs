@@ -6,8 +6,7 @@ use crypto::sha2::Sha256;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::io;
+use std::io::Read;
 
 use crate::error::DownloadError;
 
@@ -39,6 +38,19 @@ pub struct CachedImage {
     pub size: u64,
 }
 
+/// Summary of what the cache holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of cached chapters
+    pub chapters: usize,
+    /// Number of cached images
+    pub images: usize,
+    /// Total size of the cached images in bytes
+    pub total_size: u64,
+    /// Number of chapters older than the maximum age
+    pub expired_chapters: usize,
+}
+
 /// Main cache manager
 #[derive(Debug)]
 pub struct CacheManager {
@@ -58,14 +70,14 @@ impl CacheManager {
         // Ensure the cache directory exists
         if !cache_dir.exists() {
             fs::create_dir_all(&cache_dir)
-                .map_err(|e| DownloadError::IoError(e))?;
+                .map_err(DownloadError::IoError)?;
         }
 
         let index_path = cache_dir.join("index.json");
         let index = if index_path.exists() {
             // Load existing index
             let file = File::open(&index_path)
-                .map_err(|e| DownloadError::IoError(e))?;
+                .map_err(DownloadError::IoError)?;
             serde_json::from_reader(file)
                 .map_err(|e| DownloadError::ParsingError(format!("Failed to parse cache index: {}", e)))?
         } else {
@@ -85,7 +97,7 @@ impl CacheManager {
     pub fn save_index(&self) -> Result<(), DownloadError> {
         let index_path = self.cache_dir.join("index.json");
         let file = File::create(&index_path)
-            .map_err(|e| DownloadError::IoError(e))?;
+            .map_err(DownloadError::IoError)?;
 
         serde_json::to_writer_pretty(file, &self.index)
             .map_err(|e| DownloadError::ParsingError(format!("Failed to write cache index: {}", e)))?;
@@ -152,27 +164,29 @@ impl CacheManager {
         }
 
         // Generate a cache path for the image
-        let cache_filename = format!("{}.jpg", compute_hash(image_url));
+        let extension = image_path.extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("jpg");
+        let cache_filename = format!("{}.{}", compute_hash(image_url), extension);
         let cache_subdir = compute_hash(chapter_url).chars().take(2).collect::<String>();
         let cache_relpath = Path::new(&cache_subdir).join(&cache_filename);
         let cache_fullpath = self.cache_dir.join(&cache_relpath);
 
         // Ensure the cache subdirectory exists
-        if let Some(parent) = cache_fullpath.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent)
-                    .map_err(|e| DownloadError::IoError(e))?;
-            }
+        if let Some(parent) = cache_fullpath.parent()
+            && !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(DownloadError::IoError)?;
         }
 
         // Copy the image to the cache
         fs::copy(image_path, &cache_fullpath)
-            .map_err(|e| DownloadError::IoError(e))?;
+            .map_err(DownloadError::IoError)?;
 
         // Calculate checksum and file size
         let checksum = calculate_file_checksum(&cache_fullpath)?;
         let size = fs::metadata(&cache_fullpath)
-            .map_err(|e| DownloadError::IoError(e))?
+            .map_err(DownloadError::IoError)?
             .len();
 
         // Update the cache index
@@ -234,7 +248,7 @@ impl CacheManager {
         let mut valid_items = 0;
         let mut invalid_items = 0;
 
-        for (_, chapter) in &self.index {
+        for chapter in self.index.values() {
             for image in &chapter.images {
                 let image_path = self.cache_dir.join(&image.path);
 
@@ -261,19 +275,19 @@ impl CacheManager {
     pub fn clear_cache(&mut self) -> Result<(), DownloadError> {
         // Remove all files in the cache directory (except the index file)
         let entries = fs::read_dir(&self.cache_dir)
-            .map_err(|e| DownloadError::IoError(e))?;
+            .map_err(DownloadError::IoError)?;
 
         for entry in entries {
-            let entry = entry.map_err(|e| DownloadError::IoError(e))?;
+            let entry = entry.map_err(DownloadError::IoError)?;
             let path = entry.path();
 
-            if path.file_name().map_or(false, |name| name != "index.json") {
+            if path.file_name().is_some_and(|name| name != "index.json") {
                 if path.is_dir() {
                     fs::remove_dir_all(&path)
-                        .map_err(|e| DownloadError::IoError(e))?;
+                        .map_err(DownloadError::IoError)?;
                 } else {
                     fs::remove_file(&path)
-                        .map_err(|e| DownloadError::IoError(e))?;
+                        .map_err(DownloadError::IoError)?;
                 }
             }
         }
@@ -285,6 +299,66 @@ impl CacheManager {
         Ok(())
     }
 
+    /// Count the cached chapters and images and their size
+    pub fn stats(&self) -> CacheStats {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut stats = CacheStats { chapters: self.index.len(), ..CacheStats::default() };
+        for chapter in self.index.values() {
+            stats.images += chapter.images.len();
+            stats.total_size += chapter.images.iter().map(|image| image.size).sum::<u64>();
+            if now.saturating_sub(chapter.timestamp) > self.max_age {
+                stats.expired_chapters += 1;
+            }
+        }
+        stats
+    }
+
+    /// Drop chapters with missing or corrupted images, and delete files the index doesn't know
+    ///
+    /// A chapter with a single bad image is dropped as a whole, so it is downloaded again
+    /// instead of being exported with a page missing. Returns the number of chapters dropped
+    /// and of files deleted.
+    pub fn repair(&mut self) -> Result<(usize, usize), DownloadError> {
+        let broken = self.index.iter()
+            .filter(|(_, chapter)| chapter.images.iter().any(|image| {
+                let path = self.cache_dir.join(&image.path);
+                !path.exists() || calculate_file_checksum(&path).ok().as_ref() != Some(&image.checksum)
+            }))
+            .map(|(url, _)| url.clone())
+            .collect::<Vec<_>>();
+        for url in &broken {
+            self.index.remove(url);
+        }
+
+        // Every file still referenced by the index is kept, the rest is removed
+        let referenced = self.index.values()
+            .flat_map(|chapter| chapter.images.iter().map(|image| self.cache_dir.join(&image.path)))
+            .collect::<std::collections::HashSet<_>>();
+        let mut removed_files = 0;
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(&path)? {
+                let file = file?.path();
+                if file.is_file() && !referenced.contains(&file) {
+                    fs::remove_file(&file)?;
+                    removed_files += 1;
+                }
+            }
+            // Ignore the error when the directory isn't empty
+            let _ = fs::remove_dir(&path);
+        }
+
+        self.save_index()?;
+        Ok((broken.len(), removed_files))
+    }
+
     /// Remove expired items from the cache
     pub fn clean_expired(&mut self) -> Result<usize, DownloadError> {
         let now = SystemTime::now()
@@ -346,23 +420,23 @@ fn extract_chapter_title(url: &str) -> String {
 }
 
 /// Compute a hash of the given string
-fn compute_hash(input: &str) -> String {
+pub(crate) fn compute_hash(input: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.input_str(input);
     hasher.result_str()
 }
 
 /// Calculate SHA-256 checksum of a file
-fn calculate_file_checksum(file_path: &Path) -> Result<String, DownloadError> {
+pub(crate) fn calculate_file_checksum(file_path: &Path) -> Result<String, DownloadError> {
     let mut file = File::open(file_path)
-        .map_err(|e| DownloadError::IoError(e))?;
+        .map_err(DownloadError::IoError)?;
 
     let mut hasher = Sha256::new();
     let mut buffer = [0; 4096];
 
     loop {
         let bytes_read = file.read(&mut buffer)
-            .map_err(|e| DownloadError::IoError(e))?;
+            .map_err(DownloadError::IoError)?;
 
         if bytes_read == 0 {
             break;
@@ -379,7 +453,7 @@ mod tests {
     use super::*;
     use std::fs;
     use std::path::PathBuf;
-    use std::io::Write;
+    use std::io::{self, Write};
 
     // Helper to create a temporary test directory
     fn setup_test_cache_dir() -> PathBuf {
@@ -485,4 +559,30 @@ mod tests {
         // Clean up
         cleanup_test_cache_dir(&cache_dir);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_stats_and_repair() {
+        let cache_dir = std::env::temp_dir().join("manga_downloader_test_cache_repair");
+        let _ = fs::remove_dir_all(&cache_dir);
+        let temp_dir = cache_dir.join("temp");
+        create_test_image(&temp_dir.join("1.jpg"), b"first image").unwrap();
+        create_test_image(&temp_dir.join("2.jpg"), b"second image").unwrap();
+        let mut cache = CacheManager::new(cache_dir.clone(), 1).unwrap();
+
+        cache.cache_image("https://example.com/c/1", "https://example.com/1.jpg", &temp_dir.join("1.jpg")).unwrap();
+        let corrupted = cache.cache_image("https://example.com/c/2", "https://example.com/2.jpg", &temp_dir.join("2.jpg")).unwrap();
+        fs::remove_dir_all(&temp_dir).unwrap();
+
+        let stats = cache.stats();
+        assert_eq!((stats.chapters, stats.images, stats.total_size), (2, 2, 23));
+
+        fs::write(&corrupted, b"changed").unwrap();
+        let (chapters, _) = cache.repair().unwrap();
+        assert_eq!(chapters, 1);
+        assert!(cache.is_chapter_cached("https://example.com/c/1"));
+        assert!(!cache.is_chapter_cached("https://example.com/c/2"));
+        assert!(!corrupted.exists());
+
+        cleanup_test_cache_dir(&cache_dir);
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use log::{debug, info, trace};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::error::DownloadError;
+use crate::manga_to_download::ChapterInfo;
+use crate::metadata::SeriesMetadata;
+
+/// Metadata written to the `ComicInfo.xml` of a CBZ archive
+///
+/// This is the de facto standard read by Komga, Kavita and most comic readers.
+#[derive(Debug, Clone, Default)]
+pub struct ComicInfo {
+    /// Name of the series (the manga title)
+    pub series: String,
+    /// Title of the chapter
+    pub title: String,
+    /// Chapter number, e.g. "12" or "12.5"
+    pub number: Option<String>,
+    /// Volume number
+    pub volume: Option<u32>,
+    /// Description of the series
+    pub summary: Option<String>,
+    /// Year the series started
+    pub year: Option<u32>,
+    /// Authors of the series, comma-separated
+    pub writer: Option<String>,
+    /// Artists of the series, comma-separated
+    pub penciller: Option<String>,
+    /// Genres of the series, comma-separated
+    pub genre: Option<String>,
+    /// Tags of the series, comma-separated
+    pub tags: Option<String>,
+    /// Number of pages in the archive
+    pub page_count: usize,
+    /// URL the chapter was downloaded from
+    pub web: Option<String>,
+}
+
+impl ComicInfo {
+    /// Build the metadata of a chapter, taking its numbers from [`ChapterInfo::number`]
+    pub fn for_chapter(series: &str, chapter: &ChapterInfo, page_count: usize) -> Self {
+        Self {
+            series: series.to_string(),
+            title: chapter.title.clone(),
+            number: chapter.number.chapter.map(|number| number.to_string()),
+            volume: chapter.number.volume,
+            page_count,
+            web: Some(chapter.url.clone()),
+            ..Self::default()
+        }
+    }
+
+    /// Add what is known about the series, such as its authors and description
+    pub fn with_series(mut self, metadata: &SeriesMetadata) -> Self {
+        let list = |values: &[String]| (!values.is_empty()).then(|| values.join(", "));
+        self.summary = metadata.description.clone();
+        self.year = metadata.release_year;
+        self.writer = list(&metadata.authors);
+        self.penciller = list(&metadata.artists);
+        self.genre = list(&metadata.genres);
+        self.tags = list(&metadata.tags);
+        self
+    }
+
+    /// Render the metadata as a `ComicInfo.xml` document
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n");
+        push_element(&mut xml, "Series", &self.series);
+        push_element(&mut xml, "Title", &self.title);
+        if let Some(ref number) = self.number {
+            push_element(&mut xml, "Number", number);
+        }
+        if let Some(volume) = self.volume {
+            push_element(&mut xml, "Volume", &volume.to_string());
+        }
+        if let Some(ref summary) = self.summary {
+            push_element(&mut xml, "Summary", summary);
+        }
+        if let Some(year) = self.year {
+            push_element(&mut xml, "Year", &year.to_string());
+        }
+        for (name, value) in [("Writer", &self.writer), ("Penciller", &self.penciller), ("Genre", &self.genre), ("Tags", &self.tags)] {
+            if let Some(value) = value {
+                push_element(&mut xml, name, value);
+            }
+        }
+        push_element(&mut xml, "PageCount", &self.page_count.to_string());
+        if let Some(ref web) = self.web {
+            push_element(&mut xml, "Web", web);
+        }
+        push_element(&mut xml, "Manga", "Yes");
+        xml.push_str("</ComicInfo>\n");
+        xml
+    }
+}
+
+/// Packs the page images of a chapter, in order, into a CBZ archive with a `ComicInfo.xml`
+pub fn create_cbz_from_images(image_paths: &[impl AsRef<Path>], output_path: &Path, info: &ComicInfo) -> Result<(), DownloadError> {
+    if image_paths.is_empty() {
+        return Err(DownloadError::ArchiveError(String::from("Cannot create CBZ: no images provided")));
+    }
+
+    debug!("Creating CBZ from {} images", image_paths.len());
+    trace!("Output path: {:?}", output_path);
+
+    let mut archive = ZipWriter::new(File::create(output_path)?);
+    // Images are already compressed, deflating them again only costs time
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    archive.start_file("ComicInfo.xml", deflated)?;
+    archive.write_all(info.to_xml().as_bytes())?;
+
+    // Readers sort entries by name, so pages get zero-padded names in reading order
+    for (i, path) in image_paths.iter().enumerate() {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("jpg");
+        let name = format!("{:04}.{}", i + 1, extension);
+        trace!("Adding {:?} as {}", path, name);
+
+        archive.start_file(name, stored)?;
+        io::copy(&mut File::open(path)?, &mut archive)?;
+    }
+
+    archive.finish()?;
+    info!("CBZ created successfully with {} pages", image_paths.len());
+
+    Ok(())
+}
+
+fn push_element(xml: &mut String, name: &str, value: &str) {
+    xml.push_str(&format!("  <{}>{}</{}>\n", name, escape_xml(value), name));
+}
+
+/// Escape text for use in XML content and attribute values
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(title: &str, url: &str) -> ChapterInfo {
+        ChapterInfo::new(0, title.to_string(), url.to_string())
+    }
+
+    #[test]
+    fn test_comic_info_numbers() {
+        let number = |title: &str| ComicInfo::for_chapter("Example", &chapter(title, "https://example.com/c/"), 1).number;
+        assert_eq!(number("Chapter 12"), Some(String::from("12")));
+        assert_eq!(number("Chapter 12.5 - The Return"), Some(String::from("12.5")));
+        assert_eq!(number("Solo Leveling Ch. 3"), Some(String::from("3")));
+        assert_eq!(number("Prologue"), None);
+
+        let info = ComicInfo::for_chapter("Example", &chapter("Vol.2 Chapter 10", "https://example.com/c/10"), 1);
+        assert_eq!(info.volume, Some(2));
+        assert!(info.to_xml().contains("<Volume>2</Volume>"));
+    }
+
+    #[test]
+    fn test_comic_info_xml() {
+        let info = ComicInfo::for_chapter("Tom & Jerry", &chapter("Chapter 7 <Finale>", "https://example.com/c/7"), 20);
+        let xml = info.to_xml();
+
+        assert!(xml.contains("<Series>Tom &amp; Jerry</Series>"));
+        assert!(xml.contains("<Title>Chapter 7 &lt;Finale&gt;</Title>"));
+        assert!(xml.contains("<Number>7</Number>"));
+        assert!(xml.contains("<PageCount>20</PageCount>"));
+        assert!(xml.contains("<Web>https://example.com/c/7</Web>"));
+        assert!(!xml.contains("<Writer>"));
+
+        let metadata = SeriesMetadata {
+            authors: vec![String::from("Jane Doe"), String::from("John Roe")],
+            genres: vec![String::from("Action")],
+            release_year: Some(2019),
+            ..SeriesMetadata::default()
+        };
+        let xml = info.with_series(&metadata).to_xml();
+        assert!(xml.contains("<Writer>Jane Doe, John Roe</Writer>"));
+        assert!(xml.contains("<Genre>Action</Genre>"));
+        assert!(xml.contains("<Year>2019</Year>"));
+        assert!(!xml.contains("<Summary>"));
+    }
+
+    #[test]
+    fn test_create_cbz_from_images() {
+        let dir = std::env::temp_dir().join("manga_downloader_cbz_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let pages = [dir.join("image_0000.png"), dir.join("image_0001.jpg")];
+        std::fs::write(&pages[0], b"first").unwrap();
+        std::fs::write(&pages[1], b"second").unwrap();
+
+        let output = dir.join("chapter.cbz");
+        let info = ComicInfo::for_chapter("Example", &chapter("Chapter 1", "https://example.com/c/1"), pages.len());
+        create_cbz_from_images(&pages, &output, &info).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let names = archive.file_names().map(String::from).collect::<Vec<_>>();
+        assert_eq!(names, vec!["ComicInfo.xml", "0001.png", "0002.jpg"]);
+
+        let mut content = String::new();
+        io::Read::read_to_string(&mut archive.by_name("0002.jpg").unwrap(), &mut content).unwrap();
+        assert_eq!(content, "second");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
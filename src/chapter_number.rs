@@ -0,0 +1,241 @@
+use std::cmp::Ordering;
+
+/// Prefixes a chapter number follows in titles such as "Ch. 12" or "Episode 3"
+const CHAPTER_PREFIXES: [&str; 5] = ["chapter", "episode", "ch.", "ep.", "ch"];
+
+/// Prefixes a volume number follows in titles such as "Vol.3 Chapter 12"
+const VOLUME_PREFIXES: [&str; 3] = ["volume", "vol.", "vol"];
+
+/// Words marking side stories and extra chapters
+const SIDE_STORY_WORDS: [&str; 2] = ["side story", "side-story"];
+const EXTRA_WORDS: [&str; 4] = ["extra", "special", "bonus", "omake"];
+
+/// Separators that start a subtitle, e.g. "Chapter 12 - A Special Day"
+const SUBTITLE_SEPARATORS: [&str; 4] = [" - ", ": ", " – ", " — "];
+
+/// Whether a chapter belongs to the main story
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChapterKind {
+    #[default]
+    Regular,
+    /// Extra, special, bonus or omake chapters
+    Extra,
+    /// Side stories, which often have their own numbering
+    SideStory,
+}
+
+/// Volume, chapter number and subtitle of a chapter, parsed from its title or URL
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChapterNumber {
+    pub volume: Option<u32>,
+    /// Chapter number, e.g. 10.5 for "Chapter 10.5"
+    pub chapter: Option<f64>,
+    pub kind: ChapterKind,
+    /// What follows the number, e.g. "The Return" for "Chapter 12 - The Return"
+    pub subtitle: Option<String>,
+}
+
+impl ChapterNumber {
+    /// Parse a Madara chapter title such as "Vol.2 Chapter 10.5 - The Return"
+    ///
+    /// When the title has no chapter number, the number is taken from a URL such as
+    /// `.../chapter-10-5/`, where Madara writes decimals with a dash.
+    pub fn parse(title: &str, url: &str) -> Self {
+        // ASCII lowercasing keeps byte offsets valid for slicing the original title
+        let lower = title.to_ascii_lowercase();
+
+        // Only whole words before the subtitle count, so neither "Extraordinary" nor
+        // "Chapter 12 - A Special Day" is an extra
+        let head = SUBTITLE_SEPARATORS.iter()
+            .filter_map(|separator| lower.find(separator))
+            .min()
+            .map_or(lower.as_str(), |end| &lower[..end]);
+        let kind = if SIDE_STORY_WORDS.iter().any(|word| contains_word(head, word)) {
+            ChapterKind::SideStory
+        } else if EXTRA_WORDS.iter().any(|word| contains_word(head, word)) {
+            ChapterKind::Extra
+        } else {
+            ChapterKind::Regular
+        };
+
+        let volume = number_after(&lower, &VOLUME_PREFIXES)
+            .and_then(|(number, _)| number.parse::<f64>().ok())
+            .map(|volume| volume as u32);
+
+        let keywords: &[&str] = match kind {
+            ChapterKind::SideStory => &SIDE_STORY_WORDS,
+            ChapterKind::Extra => &EXTRA_WORDS,
+            ChapterKind::Regular => &[],
+        };
+        let found = number_after(&lower, &CHAPTER_PREFIXES)
+            .or_else(|| number_after(&lower, keywords))
+            .or_else(|| {
+                // Titles that are only a number, e.g. "12 - The Return"
+                let number = leading_number(&lower);
+                (!number.is_empty()).then(|| (number.to_string(), number.len()))
+            });
+
+        let (chapter, subtitle) = match found {
+            Some((number, end)) => {
+                let subtitle = title[end..].trim_start_matches([' ', ':', '-', '–', '—', '.']).trim();
+                (number.parse().ok(), (!subtitle.is_empty()).then(|| subtitle.to_string()))
+            }
+            None => (url_chapter_number(url), None),
+        };
+
+        ChapterNumber { volume, chapter, kind, subtitle }
+    }
+
+    /// File name of the chapter that stays the same whatever the site does to the title,
+    /// e.g. `chapter-0010.5` or `vol-02-extra-0045`; `None` without a chapter number
+    pub fn file_stem(&self) -> Option<String> {
+        let chapter = self.chapter?;
+        let kind = match self.kind {
+            ChapterKind::Regular => "chapter",
+            ChapterKind::Extra => "extra",
+            ChapterKind::SideStory => "side-story",
+        };
+
+        // Zero-padded so the files sort in reading order by name
+        let number = chapter.to_string();
+        let number = match number.split_once('.') {
+            Some((whole, fraction)) => format!("{:0>4}.{}", whole, fraction),
+            None => format!("{:0>4}", number),
+        };
+
+        Some(match self.volume {
+            Some(volume) => format!("vol-{:02}-{}-{}", volume, kind, number),
+            None => format!("{}-{}", kind, number),
+        })
+    }
+}
+
+/// Sort chapters (in site order, oldest first) by chapter number
+///
+/// Only regular numbered chapters are moved; extras, side stories and unnumbered chapters
+/// stay right after the chapter they followed, and the sort is stable so chapters with the
+/// same number keep their order.
+pub fn sort_by_number<T>(chapters: &mut Vec<T>, number: impl Fn(&T) -> &ChapterNumber) {
+    let mut previous = f64::NEG_INFINITY;
+    let keys = chapters.iter()
+        .map(|chapter| {
+            let number = number(chapter);
+            if number.kind == ChapterKind::Regular
+                && let Some(chapter) = number.chapter
+            {
+                previous = chapter;
+            }
+            previous
+        })
+        .collect::<Vec<_>>();
+
+    let mut keyed = keys.into_iter().zip(chapters.drain(..)).collect::<Vec<_>>();
+    keyed.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    chapters.extend(keyed.into_iter().map(|(_, chapter)| chapter));
+}
+
+/// Whether `text` has no letter or digit right before byte offset `start`
+fn at_word_start(text: &str, start: usize) -> bool {
+    text[..start].chars().next_back().is_none_or(|c| !c.is_alphanumeric())
+}
+
+/// Whether `word` appears in `text` as a whole word
+fn contains_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(start, _)| {
+        let end = start + word.len();
+        at_word_start(text, start) && text[end..].chars().next().is_none_or(|c| !c.is_alphanumeric())
+    })
+}
+
+/// The first number following one of `prefixes` at the start of a word, and the byte offset
+/// where it ends
+fn number_after(text: &str, prefixes: &[&str]) -> Option<(String, usize)> {
+    prefixes.iter().find_map(|prefix| {
+        text.match_indices(prefix).find_map(|(start, _)| {
+            if !at_word_start(text, start) {
+                return None;
+            }
+
+            let rest = &text[start + prefix.len()..];
+            let skipped = rest.len() - rest.trim_start_matches([' ', '#', '.']).len();
+            let number = leading_number(&rest[skipped..]);
+            (!number.is_empty()).then(|| (number.to_string(), start + prefix.len() + skipped + number.len()))
+        })
+    })
+}
+
+/// Digits and decimal points at the start of `text`, without a trailing point
+fn leading_number(text: &str) -> &str {
+    let end = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    text[..end].trim_end_matches('.')
+}
+
+/// Chapter number from a URL segment such as `chapter-10` or `chapter-10-5`
+fn url_chapter_number(url: &str) -> Option<f64> {
+    let segment = url.split('/').find_map(|segment| segment.strip_prefix("chapter-"))?;
+    let mut parts = segment.split('-');
+    let whole = parts.next().filter(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))?;
+    match parts.next().filter(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())) {
+        Some(fraction) => format!("{}.{}", whole, fraction).parse().ok(),
+        None => whole.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(title: &str) -> ChapterNumber {
+        ChapterNumber::parse(title, "")
+    }
+
+    #[test]
+    fn test_parse_chapter_titles() {
+        let number = parse("Vol.2 Chapter 10.5 - The Return");
+        assert_eq!(number.volume, Some(2));
+        assert_eq!(number.chapter, Some(10.5));
+        assert_eq!(number.kind, ChapterKind::Regular);
+        assert_eq!(number.subtitle.as_deref(), Some("The Return"));
+
+        assert_eq!(parse("Ch. 7: Friends").chapter, Some(7.0));
+        assert_eq!(parse("Ch. 7: Friends").subtitle.as_deref(), Some("Friends"));
+        assert_eq!(parse("12").chapter, Some(12.0));
+        assert_eq!(parse("Chapter 45 Extra").kind, ChapterKind::Extra);
+        assert_eq!(parse("Chapter 45 Extra").chapter, Some(45.0));
+
+        // Words in the subtitle or inside other words don't make a chapter an extra
+        assert_eq!(parse("Chapter 12 - A Special Day").kind, ChapterKind::Regular);
+        assert_eq!(parse("Chapter 12 - A Special Day").file_stem().as_deref(), Some("chapter-0012"));
+        assert_eq!(parse("Chapter 13 Extraordinary").kind, ChapterKind::Regular);
+        assert_eq!(parse("Bonus: Beach Episode").kind, ChapterKind::Extra);
+
+        let side_story = parse("Side Story 3");
+        assert_eq!((side_story.kind, side_story.chapter), (ChapterKind::SideStory, Some(3.0)));
+        assert_eq!(parse("Special").chapter, None);
+    }
+
+    #[test]
+    fn test_parse_number_from_url() {
+        let number = ChapterNumber::parse("The Beginning", "https://example.com/manga/example/chapter-10-5/");
+        assert_eq!(number.chapter, Some(10.5));
+        assert_eq!(ChapterNumber::parse("Prologue", "https://example.com/manga/example/prologue/").chapter, None);
+    }
+
+    #[test]
+    fn test_file_stem() {
+        assert_eq!(parse("Chapter 10.5").file_stem().as_deref(), Some("chapter-0010.5"));
+        assert_eq!(parse("Vol.2 Chapter 45 Extra").file_stem().as_deref(), Some("vol-02-extra-0045"));
+        assert_eq!(parse("Prologue").file_stem(), None);
+    }
+
+    #[test]
+    fn test_sort_by_number() {
+        let mut chapters = ["Chapter 2", "Chapter 1", "Extra", "Chapter 3", "Chapter 2.5"]
+            .map(|title| (title, parse(title)))
+            .to_vec();
+        sort_by_number(&mut chapters, |(_, number)| number);
+
+        let titles = chapters.iter().map(|(title, _)| *title).collect::<Vec<_>>();
+        assert_eq!(titles, ["Chapter 1", "Extra", "Chapter 2", "Chapter 2.5", "Chapter 3"]);
+    }
+}
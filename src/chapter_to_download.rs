@@ -1,4 +1,8 @@
+use std::sync::Arc;
+
 use crate::error::DownloadError;
+use crate::http::HttpClient;
+use crate::source::Source;
 
 pub struct ChapterToDownload {
   pub link: String,
@@ -6,19 +10,20 @@ pub struct ChapterToDownload {
   pub title: String,
   pub images: Vec<String>,
   pub document: scraper::Html,
+  pub source: Arc<dyn Source>,
 }
 
 impl ChapterToDownload {
-  pub async fn new(link: String) -> Result<Self, DownloadError> {
-      let response = reqwest::get(&link).await?;
-      let body = response.text().await?;
-      let document = scraper::Html::parse_document(&body.trim());
+  pub async fn new(link: String, source: Arc<dyn Source>, client: &HttpClient) -> Result<Self, DownloadError> {
+      let body = client.get_text(&link).await?;
+      let document = scraper::Html::parse_document(body.trim());
       let mut chapter = Self {
           link: link.clone(),
           url: link,
           title: String::new(),
           images: Vec::new(),
-          document
+          document,
+          source,
       };
       chapter.process_title()?;
       println!("processing images of chapter");
@@ -27,30 +32,12 @@ impl ChapterToDownload {
   }
 
   fn process_title(&mut self) -> Result<(), DownloadError> {
-      let title_selector = scraper::Selector::parse("#chapter-heading")
-          .map_err(|_| DownloadError::SelectorError(String::from("Failed to parse #chapter-heading selector")))?;
-
-      let title = self.document.select(&title_selector).next()
-          .ok_or_else(|| DownloadError::ElementNotFound(String::from("Chapter heading element not found")))?;
-
-      self.title = title.text().collect::<Vec<_>>().join(" ");
+      self.title = self.source.chapter_title(&self.document)?;
       Ok(())
   }
 
   pub fn process_images(&mut self) -> Result<(), DownloadError> {
-      let images_selector = scraper::Selector::parse(".page-break img")
-          .map_err(|_| DownloadError::SelectorError(String::from("Failed to parse .page-break img selector")))?;
-
-      let images = self.document.select(&images_selector).map(|e| {
-          if let Some(src) = e.attr("src") {
-              src.trim().to_string()
-          } else {
-              match e.attr("data-cfsrc") {
-                  Some(attr) => attr.trim().to_string(),
-                  None => String::new(),
-              }
-          }
-      }).filter(|url| !url.is_empty()).collect::<Vec<_>>();
+      let images = self.source.page_images(&self.document)?;
 
       if images.is_empty() {
           return Err(DownloadError::ElementNotFound(String::from("No images found in chapter")));
@@ -59,4 +46,4 @@ impl ChapterToDownload {
       self.images = images;
       Ok(())
   }
-}
\ No newline at end of file
+}
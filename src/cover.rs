@@ -0,0 +1,68 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::JpegEncoder;
+use log::{debug, info};
+
+use crate::downloader::{image_extension, write_file_atomic};
+use crate::error::DownloadError;
+use crate::http::HttpClient;
+
+/// Name of the series cover in the output directory, where Komga and Kavita look for it
+pub const COVER_FILE_NAME: &str = "cover.jpg";
+
+/// JPEG quality used when a cover served in another format is converted
+const COVER_QUALITY: u8 = 90;
+
+/// Download the cover of a series into `output_dir` as `cover.jpg`
+///
+/// `referer` should be the URL of the series page, since many image CDNs reject requests
+/// without it. Returns the path of the saved cover.
+pub async fn download_cover(client: &HttpClient, cover_url: &str, referer: &str, output_dir: &Path) -> Result<PathBuf, DownloadError> {
+    debug!("Downloading cover from {}", cover_url);
+    let bytes = client.get_bytes(cover_url, Some(referer)).await?;
+
+    let path = output_dir.join(COVER_FILE_NAME);
+    write_file_atomic(&path, &cover_jpeg(bytes)?)?;
+    info!("Saved cover to {:?}", path);
+    Ok(path)
+}
+
+/// The cover the site served, as JPEG
+///
+/// JPEG covers are kept byte for byte; PNG, WebP and other formats are converted so the
+/// file matches its name.
+fn cover_jpeg(bytes: Vec<u8>) -> Result<Vec<u8>, DownloadError> {
+    match image_extension(&bytes) {
+        Some("jpg") => Ok(bytes),
+        Some(_) => {
+            let image = image::load_from_memory(&bytes)
+                .map_err(|e| DownloadError::ImageProcessingError(format!("Failed to decode cover: {}", e)))?;
+            let mut jpeg = Cursor::new(Vec::new());
+            image.to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, COVER_QUALITY))
+                .map_err(|e| DownloadError::ImageProcessingError(format!("Failed to encode cover: {}", e)))?;
+            Ok(jpeg.into_inner())
+        }
+        None => Err(DownloadError::ImageProcessingError(String::from("Cover is not an image"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cover_is_converted_to_jpeg() {
+        let mut png = Cursor::new(Vec::new());
+        image::RgbImage::new(8, 12).write_to(&mut png, image::ImageFormat::Png).unwrap();
+
+        let jpeg = cover_jpeg(png.into_inner()).unwrap();
+        assert_eq!(image_extension(&jpeg), Some("jpg"));
+        assert_eq!(image::load_from_memory(&jpeg).unwrap().height(), 12);
+
+        // JPEG covers are kept as they are
+        assert_eq!(cover_jpeg(jpeg.clone()).unwrap(), jpeg);
+        assert!(cover_jpeg(b"<html></html>".to_vec()).is_err());
+    }
+}
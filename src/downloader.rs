@@ -1,4 +1,4 @@
-use std::{fs, path::{Path, PathBuf}, sync::Arc, time::Duration};
+use std::{fs, path::{Path, PathBuf}, sync::Arc};
 use futures::{stream, StreamExt};
 use tokio::sync::Semaphore;
 use std::env;
@@ -7,70 +7,237 @@ use std::fmt::Write;
 use tokio::io::AsyncWriteExt;
 
 use crate::error::DownloadError;
+use crate::http::HttpClient;
+use crate::placeholder::create_missing_page;
+
+/// Size used for placeholder pages when no page of the chapter could be measured
+const DEFAULT_PAGE_SIZE: (u32, u32) = (800, 1200);
+
+/// What to do with a chapter when some of its pages failed to download
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MissingPagePolicy {
+    /// Don't export the chapter at all
+    Fail,
+    /// Export the chapter without the missing pages
+    Skip,
+    /// Export the chapter with a generated "page N missing" page in place of each missing page
+    Placeholder,
+}
 
-/// Downloads a single image from a URL to a specified path
-pub async fn download_image(url: &str, path: &Path, progress_bar: Option<&ProgressBar>) -> Result<(), DownloadError> {
-    // Create a client with a longer timeout for slow connections
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(60))
-        .build()?;
-
-    let response = client.get(url).send().await?;
-
-    // Check if the response was successful
-    if !response.status().is_success() {
-        if let Some(pb) = progress_bar {
-            pb.abandon_with_message(format!("Failed: HTTP error {}", response.status()));
-        }
-        return Err(DownloadError::ParsingError(
-            format!("HTTP error: {} for URL {}", response.status(), url)
-        ));
+/// A page image that was downloaded
+#[derive(Debug, Clone)]
+pub struct DownloadedPage {
+    /// Position of the page in the chapter, starting at 0
+    pub index: usize,
+    /// URL the image was downloaded from
+    pub url: String,
+    /// Where the image was saved
+    pub path: PathBuf,
+}
+
+/// A page image that could not be downloaded
+#[derive(Debug)]
+pub struct FailedPage {
+    /// Position of the page in the chapter, starting at 0
+    pub index: usize,
+    /// URL of the image
+    pub url: String,
+    /// Why the download failed
+    pub error: DownloadError,
+}
+
+/// Outcome of downloading the page images of one chapter
+#[derive(Debug, Default)]
+pub struct ChapterDownloadReport {
+    /// Pages that were downloaded
+    pub succeeded: Vec<DownloadedPage>,
+    /// Pages that failed even after retrying
+    pub failed: Vec<FailedPage>,
+    /// Positions (starting at 0) of the pages found to be double-page spreads once processed
+    pub spreads: Vec<usize>,
+}
+
+impl ChapterDownloadReport {
+    /// Whether every page of the chapter was downloaded
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
     }
 
-    // Get the total size for progress tracking
-    let total_size = response.content_length().unwrap_or(0);
-    if let Some(pb) = progress_bar {
-        pb.set_length(total_size);
+    /// Total number of pages in the chapter, downloaded or not
+    pub fn page_count(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+
+    /// One-line summary of the chapter, such as "12 of 14 pages, pages 3, 7 missing, spreads on pages 9"
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} of {} pages", self.succeeded.len(), self.page_count());
+        if !self.failed.is_empty() {
+            summary.push_str(&format!(", pages {} missing", page_numbers(self.failed.iter().map(|page| page.index))));
+        }
+        if !self.spreads.is_empty() {
+            summary.push_str(&format!(", spreads on pages {}", page_numbers(self.spreads.iter().copied())));
+        }
+        summary
     }
 
-    // Create the file
-    let mut file = tokio::fs::File::create(path).await
-        .map_err(|e| DownloadError::IoError(e))?;
+    /// Resolve the images to export for this chapter, in page order, according to `policy`
+    ///
+    /// Placeholder pages are written to `chapter_dir`.
+    pub fn pages_for_export(&self, policy: MissingPagePolicy, chapter_dir: &Path) -> Result<Vec<PathBuf>, DownloadError> {
+        if !self.is_complete() && policy == MissingPagePolicy::Fail {
+            return Err(DownloadError::MissingPages(format!(
+                "{} of {} pages failed to download (pages {})",
+                self.failed.len(), self.page_count(), page_numbers(self.failed.iter().map(|page| page.index))
+            )));
+        }
 
-    // Stream the download with progress updates
-    let stream = response.bytes();
+        let mut pages = self.succeeded.iter()
+            .map(|page| (page.index, page.path.clone()))
+            .collect::<Vec<_>>();
 
-    match stream.await {
-        Ok(bytes) => {
-            let downloaded = bytes.len() as u64;
-            if let Some(pb) = progress_bar {
-                pb.set_position(downloaded);
+        if policy == MissingPagePolicy::Placeholder && !self.failed.is_empty() {
+            let (width, height) = self.succeeded.iter()
+                .find_map(|page| image_dimensions(&page.path))
+                .unwrap_or(DEFAULT_PAGE_SIZE);
+
+            for page in &self.failed {
+                let path = chapter_dir.join(format!("missing_{:04}.png", page.index));
+                create_missing_page(&path, page.index + 1, width, height)?;
+                pages.push((page.index, path));
             }
+        }
 
-            file.write_all(&bytes).await
-                .map_err(|e| DownloadError::IoError(e))?;
+        pages.sort_by_key(|(index, _)| *index);
+        Ok(pages.into_iter().map(|(_, path)| path).collect())
+    }
+}
 
-            if let Some(pb) = progress_bar {
-                pb.finish_with_message("Complete");
-            }
-        },
+/// Page numbers as shown to readers, starting at 1, for the given page positions
+fn page_numbers(indices: impl Iterator<Item = usize>) -> String {
+    indices.map(|index| (index + 1).to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Picks the file extension matching the format of an image from its magic bytes
+pub fn image_extension(bytes: &[u8]) -> Option<&'static str> {
+    image::guess_format(bytes).ok()
+        .and_then(|format| format.extensions_str().first().copied())
+}
+
+/// Downloads a single image from a URL to a specified path
+///
+/// `referer` should be the URL of the chapter page, since many image CDNs reject requests without it.
+/// The extension of `path` is replaced by the one matching the downloaded image, so the
+/// returned path is where the image was actually saved.
+pub async fn download_image(
+    client: &HttpClient,
+    url: &str,
+    referer: Option<&str>,
+    path: &Path,
+    progress_bar: Option<&ProgressBar>
+) -> Result<PathBuf, DownloadError> {
+    let bytes = match client.get_bytes(url, referer).await {
+        Ok(bytes) => bytes,
         Err(e) => {
             if let Some(pb) = progress_bar {
                 pb.abandon_with_message(format!("Failed: {}", e));
             }
-            return Err(e.into());
+            return Err(e);
+        }
+    };
+
+    let downloaded = bytes.len() as u64;
+    if let Some(pb) = progress_bar {
+        pb.set_length(downloaded);
+        pb.set_position(downloaded);
+    }
+
+    // Error pages and placeholders served with a success status must not count as pages
+    let path = match image_extension(&bytes) {
+        Some(extension) => path.with_extension(extension),
+        None => {
+            if let Some(pb) = progress_bar {
+                pb.abandon_with_message("Failed: not an image");
+            }
+            return Err(DownloadError::ImageProcessingError(format!("Response from {} is not an image", url)));
         }
+    };
+
+    // Write to a temporary file first, so a crash never leaves a truncated image under the final name
+    let partial = partial_path(&path);
+    let mut file = tokio::fs::File::create(&partial).await
+        .map_err(DownloadError::IoError)?;
+
+    file.write_all(&bytes).await
+        .map_err(DownloadError::IoError)?;
+    // Make sure the data is on disk before the rename makes the image look complete
+    file.sync_all().await
+        .map_err(DownloadError::IoError)?;
+    drop(file);
+    tokio::fs::rename(&partial, &path).await
+        .map_err(DownloadError::IoError)?;
+
+    if let Some(pb) = progress_bar {
+        pb.finish_with_message("Complete");
     }
 
+    Ok(path)
+}
+
+/// Path of the temporary file `path` is written to before being renamed into place
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Writes `contents` to `path` through a temporary file, so readers never see a half-written file
+pub fn write_file_atomic(path: &Path, contents: &[u8]) -> Result<(), DownloadError> {
+    let partial = partial_path(path);
+    let mut file = fs::File::create(&partial)?;
+    std::io::Write::write_all(&mut file, contents)?;
+    // Make sure the data is on disk before the rename makes the file look complete
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&partial, path)?;
     Ok(())
 }
 
+/// Reads the size of an image, detecting its format from the content rather than the extension
+pub(crate) fn image_dimensions(path: &Path) -> Option<(u32, u32)> {
+    image::ImageReader::open(path).ok()?
+        .with_guessed_format().ok()?
+        .into_dimensions().ok()
+}
+
 /// Downloads multiple images concurrently with a semaphore to limit concurrency
+///
+/// Failed pages don't abort the other downloads; they are listed in the returned report.
 pub async fn download_images(
+    client: &HttpClient,
     image_urls: Vec<String>,
+    referer: Option<&str>,
     output_dir: &Path,
     concurrency: usize
-) -> Vec<std::path::PathBuf> {
+) -> ChapterDownloadReport {
+    download_remaining_images(client, image_urls, referer, output_dir, concurrency, Vec::new()).await
+}
+
+/// Like [`download_images`], but pages already in `completed` (e.g. from an interrupted run)
+/// are not downloaded again and are reported as succeeded
+pub async fn download_remaining_images(
+    client: &HttpClient,
+    image_urls: Vec<String>,
+    referer: Option<&str>,
+    output_dir: &Path,
+    concurrency: usize,
+    completed: Vec<DownloadedPage>
+) -> ChapterDownloadReport {
+    // Store the number of images in the chapter
+    let total_images = image_urls.len();
+    let image_urls = image_urls.into_iter()
+        .enumerate()
+        .filter(|(i, _)| !completed.iter().any(|page| page.index == *i))
+        .collect::<Vec<_>>();
     let semaphore = Arc::new(Semaphore::new(concurrency));
 
     // Setup progress bars
@@ -93,11 +260,8 @@ pub async fn download_images(
     .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
     .progress_chars("#>-");
 
-    // Store the number of images to download
-    let total_images = image_urls.len();
-
     let download_tasks = stream::iter(
-        image_urls.into_iter().enumerate().map(|(i, image_url)| {
+        image_urls.into_iter().map(|(i, image_url)| {
             let semaphore = Arc::clone(&semaphore);
             let output_dir = output_dir.to_path_buf();
             let img_progress_style = image_progress_style.clone();
@@ -108,22 +272,23 @@ pub async fn download_images(
                 // Acquire permit from semaphore (blocks if we hit max concurrency)
                 let _permit = semaphore.acquire().await.unwrap();
 
-                let image_path = output_dir.join(format!("image_{:03}.jpg", i));
+                // Four digits keep chapters of up to 9999 pages sorted by name
+                let image_path = output_dir.join(format!("image_{:04}.jpg", i));
 
                 // Create a progress bar for this image
                 let pb = multi_progress.add(ProgressBar::new(0));
                 pb.set_style(img_progress_style);
                 pb.set_message(format!("Image {}/{}", i + 1, total_images));
 
-                match download_image(&image_url, &image_path, Some(&pb)).await {
-                    Ok(_) => {
+                match download_image(client, &image_url, referer, &image_path, Some(&pb)).await {
+                    Ok(path) => {
                         pb.finish_with_message(format!("✓ Image {}", i + 1));
                         main_pb.inc(1);
-                        Ok(image_path)
+                        Ok(DownloadedPage { index: i, url: image_url, path })
                     },
                     Err(e) => {
                         pb.abandon_with_message(format!("✗ Failed: {}", e));
-                        Err(e)
+                        Err(FailedPage { index: i, url: image_url, error: e })
                     }
                 }
             }
@@ -135,10 +300,18 @@ pub async fn download_images(
 
     main_pb.finish_with_message("All downloads complete!");
 
-    // Filter out errors and keep successful downloads
-    download_tasks.into_iter()
-        .filter_map(Result::ok)
-        .collect()
+    let mut report = ChapterDownloadReport { succeeded: completed, ..Default::default() };
+    for result in download_tasks {
+        match result {
+            Ok(page) => report.succeeded.push(page),
+            Err(page) => report.failed.push(page),
+        }
+    }
+
+    // Downloads finish in any order, but pages must stay in reading order
+    report.succeeded.sort_by_key(|page| page.index);
+    report.failed.sort_by_key(|page| page.index);
+    report
 }
 
 /// Builds a path for a chapter directory with OS-aware path handling
@@ -152,7 +325,7 @@ pub fn build_chapter_path(output_dir: &Path, chapter_title: &str) -> std::path::
 pub fn ensure_dir_exists(path: &Path) -> Result<(), DownloadError> {
     if !path.exists() {
         std::fs::create_dir_all(path)
-            .map_err(|e| DownloadError::IoError(e))?;
+            .map_err(DownloadError::IoError)?;
     }
     Ok(())
 }
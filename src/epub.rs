@@ -0,0 +1,422 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use log::{debug, info, trace};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::cache::compute_hash;
+use crate::cbz::escape_xml;
+use crate::error::DownloadError;
+use crate::naming::utc_timestamp;
+
+/// How readers may combine two pages into a spread (`rendition:spread`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum EpubSpread {
+    /// Always show a single page
+    None,
+    /// Show two pages side by side when the device is held in landscape
+    #[default]
+    Landscape,
+    /// Show two pages side by side in any orientation
+    Both,
+    /// Let the reader decide
+    Auto,
+}
+
+impl EpubSpread {
+    fn as_str(self) -> &'static str {
+        match self {
+            EpubSpread::None => "none",
+            EpubSpread::Landscape => "landscape",
+            EpubSpread::Both => "both",
+            EpubSpread::Auto => "auto",
+        }
+    }
+}
+
+/// Book-level metadata and rendition settings of an EPUB
+#[derive(Debug, Clone)]
+pub struct EpubOptions {
+    /// Title of the book
+    pub title: String,
+    /// Language of the book, as a BCP 47 tag
+    pub language: String,
+    /// Pages are read right to left, as in most manga
+    pub right_to_left: bool,
+    /// How pages may be combined into spreads
+    pub spread: EpubSpread,
+    /// Source URL, used to derive a stable identifier for the book
+    pub source_url: String,
+    /// Authors and artists of the series
+    pub creators: Vec<String>,
+    /// Description of the series
+    pub description: Option<String>,
+    /// Genres and tags of the series
+    pub subjects: Vec<String>,
+    /// Image added as the first page and marked as the cover of the book
+    pub cover: Option<PathBuf>,
+}
+
+impl Default for EpubOptions {
+    fn default() -> Self {
+        Self {
+            title: String::from("Manga"),
+            language: String::from("en"),
+            right_to_left: true,
+            spread: EpubSpread::default(),
+            source_url: String::new(),
+            creators: Vec::new(),
+            description: None,
+            subjects: Vec::new(),
+            cover: None,
+        }
+    }
+}
+
+/// A chapter of an EPUB: a title for the navigation document and its page images in order
+#[derive(Debug, Clone)]
+pub struct EpubChapter {
+    /// Title shown in the table of contents
+    pub title: String,
+    /// Page images in reading order
+    pub image_paths: Vec<PathBuf>,
+}
+
+struct EpubPage {
+    image_name: String,
+    media_type: &'static str,
+    width: u32,
+    height: u32,
+    source: PathBuf,
+}
+
+/// Generates a fixed-layout EPUB 3 from the images of a single chapter
+pub fn create_epub_from_images(image_paths: &[impl AsRef<Path>], output_path: &Path, options: &EpubOptions) -> Result<(), DownloadError> {
+    let chapter = EpubChapter {
+        title: options.title.clone(),
+        image_paths: image_paths.iter().map(|path| path.as_ref().to_path_buf()).collect(),
+    };
+    create_epub(&[chapter], output_path, options)
+}
+
+/// Generates a fixed-layout EPUB 3 with one page per image and a navigation entry per chapter
+pub fn create_epub(chapters: &[EpubChapter], output_path: &Path, options: &EpubOptions) -> Result<(), DownloadError> {
+    if chapters.iter().all(|chapter| chapter.image_paths.is_empty()) {
+        return Err(DownloadError::ArchiveError(String::from("Cannot create EPUB: no images provided")));
+    }
+
+    debug!("Creating EPUB from {} chapters", chapters.len());
+    trace!("Output path: {:?}", output_path);
+
+    // Pages are numbered across the whole book; each chapter starts at its first page
+    // A cover is marked as the cover image and goes before the chapters
+    let mut pages = Vec::new();
+    if let Some(ref cover) = options.cover {
+        pages.push(epub_page(1, cover)?);
+    }
+    let mut chapter_starts = Vec::new();
+    for chapter in chapters {
+        if chapter.image_paths.is_empty() {
+            continue;
+        }
+        chapter_starts.push((chapter.title.as_str(), pages.len() + 1));
+
+        for path in &chapter.image_paths {
+            pages.push(epub_page(pages.len() + 1, path)?);
+        }
+    }
+
+    let mut archive = ZipWriter::new(File::create(output_path)?);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype must be the first entry and uncompressed so readers can identify the file
+    archive.start_file("mimetype", stored)?;
+    archive.write_all(b"application/epub+zip")?;
+
+    archive.start_file("META-INF/container.xml", deflated)?;
+    archive.write_all(CONTAINER_XML.as_bytes())?;
+
+    archive.start_file("OEBPS/content.opf", deflated)?;
+    archive.write_all(package_document(&pages, options).as_bytes())?;
+
+    archive.start_file("OEBPS/nav.xhtml", deflated)?;
+    archive.write_all(navigation_document(&chapter_starts, options).as_bytes())?;
+
+    for (i, page) in pages.iter().enumerate() {
+        trace!("Adding page {} from {:?}", i + 1, page.source);
+
+        archive.start_file(format!("OEBPS/pages/{:04}.xhtml", i + 1), deflated)?;
+        archive.write_all(page_document(i + 1, page, options).as_bytes())?;
+
+        archive.start_file(format!("OEBPS/images/{}", page.image_name), stored)?;
+        io::copy(&mut File::open(&page.source)?, &mut archive)?;
+    }
+
+    archive.finish()?;
+    info!("EPUB created successfully with {} pages", pages.len());
+
+    Ok(())
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+fn epub_page(number: usize, path: &Path) -> Result<EpubPage, DownloadError> {
+    let (width, height) = image_size(path)?;
+    let extension = path.extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("jpg")
+        .to_lowercase();
+    Ok(EpubPage {
+        image_name: format!("{:04}.{}", number, extension),
+        media_type: media_type(&extension),
+        width,
+        height,
+        source: path.to_path_buf(),
+    })
+}
+
+fn package_document(pages: &[EpubPage], options: &EpubOptions) -> String {
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    manifest.push_str("    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n");
+
+    for (i, page) in pages.iter().enumerate() {
+        let number = i + 1;
+        let cover = if i == 0 && options.cover.is_some() { " properties=\"cover-image\"" } else { "" };
+        manifest.push_str(&format!(
+            "    <item id=\"page{number:04}\" href=\"pages/{number:04}.xhtml\" media-type=\"application/xhtml+xml\"/>\n"
+        ));
+        manifest.push_str(&format!(
+            "    <item id=\"image{number:04}\" href=\"images/{}\" media-type=\"{}\"{cover}/>\n",
+            page.image_name, page.media_type
+        ));
+        spine.push_str(&format!("    <itemref idref=\"page{number:04}\"/>\n"));
+    }
+
+    let mut details = String::new();
+    for creator in &options.creators {
+        details.push_str(&format!("    <dc:creator>{}</dc:creator>\n", escape_xml(creator)));
+    }
+    if let Some(ref description) = options.description {
+        details.push_str(&format!("    <dc:description>{}</dc:description>\n", escape_xml(description)));
+    }
+    for subject in &options.subjects {
+        details.push_str(&format!("    <dc:subject>{}</dc:subject>\n", escape_xml(subject)));
+    }
+
+    let direction = if options.right_to_left { "rtl" } else { "ltr" };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{language}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>{language}</dc:language>
+{details}    <meta property="dcterms:modified">{modified}</meta>
+    <meta property="rendition:layout">pre-paginated</meta>
+    <meta property="rendition:orientation">auto</meta>
+    <meta property="rendition:spread">{spread}</meta>
+  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine page-progression-direction="{direction}">
+{spine}  </spine>
+</package>
+"#,
+        language = escape_xml(&options.language),
+        identifier = book_identifier(options),
+        title = escape_xml(&options.title),
+        modified = utc_timestamp(),
+        spread = options.spread.as_str(),
+    )
+}
+
+fn navigation_document(chapter_starts: &[(&str, usize)], options: &EpubOptions) -> String {
+    let entries = chapter_starts.iter()
+        .map(|(title, page)| format!("        <li><a href=\"pages/{:04}.xhtml\">{}</a></li>\n", page, escape_xml(title)))
+        .collect::<String>();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{language}">
+  <head><title>{title}</title></head>
+  <body>
+    <nav epub:type="toc" id="toc">
+      <h1>{title}</h1>
+      <ol>
+{entries}      </ol>
+    </nav>
+  </body>
+</html>
+"#,
+        language = escape_xml(&options.language),
+        title = escape_xml(&options.title),
+    )
+}
+
+fn page_document(number: usize, page: &EpubPage, options: &EpubOptions) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="{language}">
+  <head>
+    <title>Page {number}</title>
+    <meta name="viewport" content="width={width}, height={height}"/>
+    <style>html, body {{ margin: 0; padding: 0; }} img {{ display: block; width: {width}px; height: {height}px; }}</style>
+  </head>
+  <body>
+    <img src="../images/{image}" alt="Page {number}"/>
+  </body>
+</html>
+"#,
+        language = escape_xml(&options.language),
+        width = page.width,
+        height = page.height,
+        image = page.image_name,
+    )
+}
+
+/// Stable `urn:uuid` derived from the source URL and title, so re-exports keep the same identity
+fn book_identifier(options: &EpubOptions) -> String {
+    let hash = compute_hash(&format!("{}\n{}", options.source_url, options.title));
+    format!("urn:uuid:{}-{}-5{}-8{}-{}", &hash[0..8], &hash[8..12], &hash[13..16], &hash[17..20], &hash[20..32])
+}
+
+fn image_size(path: &Path) -> Result<(u32, u32), DownloadError> {
+    image::ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_dimensions()
+        .map_err(|e| DownloadError::ImageProcessingError(format!("Failed to read size of {:?}: {}", path, e)))
+}
+
+fn media_type(extension: &str) -> &'static str {
+    match extension {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => "image/jpeg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn write_png(path: &Path, width: u32, height: u32) {
+        image::RgbImage::new(width, height).save(path).unwrap();
+    }
+
+    #[test]
+    fn test_create_epub() {
+        let dir = std::env::temp_dir().join("manga_downloader_epub_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("image_0000.png");
+        let second = dir.join("image_0001.png");
+        write_png(&first, 40, 60);
+        write_png(&second, 80, 60);
+
+        let chapters = vec![
+            EpubChapter { title: String::from("Chapter 1"), image_paths: vec![first.clone()] },
+            EpubChapter { title: String::from("Chapter 2 & more"), image_paths: vec![second] },
+        ];
+        let output = dir.join("book.epub");
+        let options = EpubOptions {
+            creators: vec![String::from("Jane Doe")],
+            subjects: vec![String::from("Action")],
+            ..EpubOptions::default()
+        };
+        create_epub(&chapters, &output, &options).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        drop(mimetype);
+
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+            content
+        };
+
+        let opf = read("OEBPS/content.opf");
+        assert!(opf.contains("<meta property=\"rendition:layout\">pre-paginated</meta>"));
+        assert!(opf.contains("<meta property=\"rendition:spread\">landscape</meta>"));
+        assert!(opf.contains("page-progression-direction=\"rtl\""));
+        assert!(opf.contains("<dc:creator>Jane Doe</dc:creator>"));
+        assert!(opf.contains("<dc:subject>Action</dc:subject>"));
+        assert!(!opf.contains("<dc:description>"));
+        assert!(opf.contains("href=\"images/0002.png\" media-type=\"image/png\""));
+
+        let nav = read("OEBPS/nav.xhtml");
+        assert!(nav.contains("<a href=\"pages/0001.xhtml\">Chapter 1</a>"));
+        assert!(nav.contains("<a href=\"pages/0002.xhtml\">Chapter 2 &amp; more</a>"));
+
+        let page = read("OEBPS/pages/0002.xhtml");
+        assert!(page.contains("content=\"width=80, height=60\""));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_cover_comes_first() {
+        let dir = std::env::temp_dir().join("manga_downloader_epub_cover_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let cover = dir.join("cover.png");
+        let page = dir.join("image_0000.png");
+        write_png(&cover, 30, 45);
+        write_png(&page, 40, 60);
+
+        let chapters = vec![EpubChapter { title: String::from("Chapter 1"), image_paths: vec![page] }];
+        let output = dir.join("book.epub");
+        let options = EpubOptions { cover: Some(cover), ..EpubOptions::default() };
+        create_epub(&chapters, &output, &options).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+            content
+        };
+
+        assert!(read("OEBPS/content.opf").contains("href=\"images/0001.png\" media-type=\"image/png\" properties=\"cover-image\""));
+        assert!(read("OEBPS/pages/0001.xhtml").contains("content=\"width=30, height=45\""));
+        // The chapter starts after the cover
+        assert!(read("OEBPS/nav.xhtml").contains("<a href=\"pages/0002.xhtml\">Chapter 1</a>"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_no_cover_image_without_cover() {
+        let dir = std::env::temp_dir().join("manga_downloader_epub_no_cover_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let page = dir.join("image_0000.png");
+        write_png(&page, 40, 60);
+
+        let chapters = vec![EpubChapter { title: String::from("Chapter 1"), image_paths: vec![page] }];
+        let output = dir.join("book.epub");
+        create_epub(&chapters, &output, &EpubOptions::default()).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let mut opf = String::new();
+        archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut opf).unwrap();
+        // The first chapter page isn't declared as the cover
+        assert!(opf.contains("href=\"images/0001.png\" media-type=\"image/png\"/>"));
+        assert!(!opf.contains("cover-image"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
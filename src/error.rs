@@ -12,6 +12,10 @@ pub enum DownloadError {
     ImageProcessingError(String),
     PdfGenerationError(String),
     CacheError(String),
+    UnsupportedSource(String),
+    HttpStatus(u16, String),
+    MissingPages(String),
+    ArchiveError(String),
 }
 
 impl fmt::Display for DownloadError {
@@ -26,6 +30,10 @@ impl fmt::Display for DownloadError {
             DownloadError::ImageProcessingError(msg) => write!(f, "Image processing error: {}", msg),
             DownloadError::PdfGenerationError(msg) => write!(f, "PDF generation error: {}", msg),
             DownloadError::CacheError(msg) => write!(f, "Cache operation failed: {}", msg),
+            DownloadError::UnsupportedSource(host) => write!(f, "No source available for host: {}", host),
+            DownloadError::HttpStatus(status, url) => write!(f, "HTTP error: {} for URL {}", status, url),
+            DownloadError::MissingPages(msg) => write!(f, "Chapter is incomplete: {}", msg),
+            DownloadError::ArchiveError(msg) => write!(f, "Archive error: {}", msg),
         }
     }
 }
@@ -44,8 +52,8 @@ impl From<std::io::Error> for DownloadError {
     }
 }
 
-impl From<genpdf::error::Error> for DownloadError {
-    fn from(err: genpdf::error::Error) -> Self {
-        DownloadError::PdfGenerationError(err.to_string())
+impl From<zip::result::ZipError> for DownloadError {
+    fn from(err: zip::result::ZipError) -> Self {
+        DownloadError::ArchiveError(err.to_string())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,210 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, info, trace, warn};
+use reqwest::cookie::Jar;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, REFERER, RETRY_AFTER};
+
+use crate::error::DownloadError;
+use crate::rate_limit::{HostRateLimiter, RateLimitConfig};
+use crate::retry::{parse_retry_after, RetryPolicy};
+
+/// User-Agent sent when none is configured
+pub const DEFAULT_USER_AGENT: &str = concat!("download-manga/", env!("CARGO_PKG_VERSION"));
+
+/// Settings for the HTTP client shared by every request of a run
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// User-Agent header sent with every request
+    pub user_agent: String,
+    /// Timeout for a whole request, including reading the body
+    pub timeout: Duration,
+    /// Extra headers sent with every request
+    pub headers: Vec<(String, String)>,
+    /// How failed requests are retried
+    pub retry: RetryPolicy,
+    /// Per-host politeness limits
+    pub rate_limit: RateLimitConfig,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: Duration::from_secs(60),
+            headers: Vec::new(),
+            retry: RetryPolicy::default(),
+            rate_limit: RateLimitConfig::default(),
+        }
+    }
+}
+
+/// HTTP client with a cookie jar, shared by every fetch of a run so connections are reused
+///
+/// Every request goes through the same retry policy and per-host rate limiter.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    cookie_jar: Arc<Jar>,
+    retry: RetryPolicy,
+    rate_limiter: HostRateLimiter,
+}
+
+impl HttpClient {
+    /// Build a client from the given settings
+    pub fn new(config: &HttpConfig) -> Result<Self, DownloadError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| DownloadError::ParsingError(format!("Invalid header name '{}': {}", name, e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| DownloadError::ParsingError(format!("Invalid value for header '{}': {}", name, e)))?;
+            headers.insert(name, value);
+        }
+
+        let cookie_jar = Arc::new(Jar::default());
+        let client = reqwest::Client::builder()
+            .user_agent(&config.user_agent)
+            .default_headers(headers)
+            .cookie_provider(Arc::clone(&cookie_jar))
+            .timeout(config.timeout)
+            .build()?;
+
+        debug!("Created HTTP client with User-Agent: {}", config.user_agent);
+        Ok(Self {
+            client,
+            cookie_jar,
+            retry: config.retry.clone(),
+            rate_limiter: HostRateLimiter::new(config.rate_limit.clone()),
+        })
+    }
+
+    /// Add a `name=value` cookie (optionally with attributes) for the host of `url`
+    pub fn add_cookie(&self, cookie: &str, url: &str) -> Result<(), DownloadError> {
+        let url = reqwest::Url::parse(url)
+            .map_err(|e| DownloadError::ParsingError(format!("Invalid cookie URL {}: {}", url, e)))?;
+        self.cookie_jar.add_cookie_str(cookie, &url);
+        Ok(())
+    }
+
+    /// Fetch a page and return its body as text
+    pub async fn get_text(&self, url: &str) -> Result<String, DownloadError> {
+        self.execute(self.client.get(url), url, |response| response.text()).await
+    }
+
+    /// Post a form and return the response body as text
+    pub async fn post_form(&self, url: &str, form: &[(String, String)]) -> Result<String, DownloadError> {
+        let request = self.client.post(url)
+            .header("X-Requested-With", "XMLHttpRequest")
+            .form(form);
+        self.execute(request, url, |response| response.text()).await
+    }
+
+    /// Download an image, sending `referer` as the Referer header since many CDNs require it
+    pub async fn get_bytes(&self, url: &str, referer: Option<&str>) -> Result<Vec<u8>, DownloadError> {
+        let mut request = self.client.get(url);
+        if let Some(referer) = referer {
+            request = request.header(REFERER, referer);
+        }
+        self.execute(request, url, |response| async move {
+            response.bytes().await.map(|bytes| bytes.to_vec())
+        }).await
+    }
+
+    /// Send a request and read its body, retrying transient failures according to the retry policy
+    async fn execute<T, F, Fut>(&self, request: reqwest::RequestBuilder, url: &str, read_body: F) -> Result<T, DownloadError>
+    where
+        F: Fn(reqwest::Response) -> Fut,
+        Fut: Future<Output = Result<T, reqwest::Error>>,
+    {
+        let max_attempts = self.retry.max_attempts.max(1);
+        let mut attempt = 1;
+
+        loop {
+            let attempt_request = request.try_clone()
+                .ok_or_else(|| DownloadError::ParsingError(format!("Request to {} cannot be retried", url)))?;
+
+            // Held until the body is read, so it bounds the requests in flight per host
+            let permit = self.rate_limiter.acquire(url).await;
+
+            let (error, retry_after) = match attempt_request.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    trace!("{} {} (attempt {}/{})", status, url, attempt, max_attempts);
+
+                    if status.is_success() {
+                        match read_body(response).await {
+                            Ok(body) => {
+                                if attempt > 1 {
+                                    info!("Request to {} succeeded on attempt {}/{}", url, attempt, max_attempts);
+                                }
+                                return Ok(body);
+                            }
+                            Err(e) => (DownloadError::RequestFailed(e), None),
+                        }
+                    } else {
+                        let error = DownloadError::HttpStatus(status.as_u16(), url.to_string());
+                        if !self.retry.is_retryable_status(status.as_u16()) {
+                            return Err(error);
+                        }
+                        let retry_after = response.headers().get(RETRY_AFTER)
+                            .and_then(|value| value.to_str().ok())
+                            .and_then(parse_retry_after);
+                        (error, retry_after)
+                    }
+                }
+                Err(e) if is_transient(&e) => (DownloadError::RequestFailed(e), None),
+                Err(e) => return Err(e.into()),
+            };
+
+            if attempt >= max_attempts {
+                if max_attempts > 1 {
+                    warn!("Giving up on {} after {} attempts: {}", url, attempt, error);
+                }
+                return Err(error);
+            }
+
+            drop(permit);
+            let delay = self.retry.delay(attempt, retry_after);
+            warn!("Attempt {}/{} for {} failed: {}; retrying in {:.1}s", attempt, max_attempts, url, error, delay.as_secs_f64());
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Whether a request error is likely to go away when retried
+fn is_transient(error: &reqwest::Error) -> bool {
+    error.is_timeout() || error.is_connect() || error.is_request() || error.is_body()
+}
+
+/// Parse a `Name: Value` header given on the command line
+pub fn parse_header(header: &str) -> Result<(String, String), DownloadError> {
+    let (name, value) = header.split_once(':')
+        .ok_or_else(|| DownloadError::ParsingError(format!("Invalid header '{}', expected 'Name: Value'", header)))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header("Accept-Language: en-US, en").unwrap(),
+            (String::from("Accept-Language"), String::from("en-US, en"))
+        );
+        assert!(parse_header("no-colon").is_err());
+    }
+
+    #[test]
+    fn test_invalid_header_is_rejected() {
+        let config = HttpConfig {
+            headers: vec![(String::from("Bad Header"), String::from("value"))],
+            ..HttpConfig::default()
+        };
+        assert!(HttpClient::new(&config).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{debug, trace, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{calculate_file_checksum, compute_hash};
+use crate::downloader::{image_dimensions, write_file_atomic, ChapterDownloadReport, DownloadedPage};
+use crate::error::DownloadError;
+
+/// Name of the journal file in the output directory
+pub const JOURNAL_FILE_NAME: &str = ".download-journal.json";
+
+/// How far a chapter or page has come
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryState {
+    /// Not downloaded yet, or the download failed
+    Pending,
+    /// The file is complete on disk
+    Downloaded,
+    /// The file was read back and is a valid image
+    Verified,
+    /// Included in an exported PDF, CBZ or EPUB
+    Exported,
+}
+
+/// Journal entry of one page image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageEntry {
+    /// URL the image is downloaded from
+    pub url: String,
+    pub state: EntryState,
+    /// Where the image was saved, relative to the output directory when possible
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Size of the saved file in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// Journal entry of one chapter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChapterEntry {
+    pub title: String,
+    /// Position of the chapter in the series, oldest first
+    #[serde(default)]
+    pub position: usize,
+    pub state: EntryState,
+    /// Page images in reading order
+    pub images: Vec<ImageEntry>,
+    /// Positions (starting at 0) of the pages that are double-page spreads
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spreads: Vec<usize>,
+}
+
+/// Journal entry of one exported file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportEntry {
+    /// Hash of the export settings and the content of every page, see [`Journal::export_fingerprint`]
+    pub fingerprint: String,
+    /// URLs of the chapters in the file
+    pub chapters: Vec<String>,
+}
+
+/// Progress of the downloads into one output directory, kept on disk so an interrupted
+/// run can be resumed without downloading or exporting anything twice
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    /// Where the journal is saved
+    #[serde(skip)]
+    path: PathBuf,
+    /// Title of the series downloaded into the directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Chapters by URL
+    #[serde(default)]
+    pub chapters: BTreeMap<String, ChapterEntry>,
+    /// Exported files by path relative to the output directory
+    #[serde(default)]
+    pub exports: BTreeMap<String, ExportEntry>,
+}
+
+impl Journal {
+    /// Load the journal of `output_dir`, starting an empty one when there is none yet
+    ///
+    /// A journal that can't be parsed is set aside with a warning, since everything it
+    /// records can be downloaded again.
+    pub fn load(output_dir: &Path) -> Result<Self, DownloadError> {
+        let path = output_dir.join(JOURNAL_FILE_NAME);
+        let mut journal: Journal = load_state_file(&path, "journal")?;
+        journal.path = path;
+        Ok(journal)
+    }
+
+    /// Save the journal, replacing the previous version atomically
+    pub fn save(&self) -> Result<(), DownloadError> {
+        save_state_file(&self.path, self, "journal")
+    }
+
+    /// Directory the journal belongs to, which relative page paths start from
+    fn base_dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+
+    /// Record that a chapter with these page images is about to be downloaded
+    ///
+    /// Progress recorded by an earlier run is kept as long as the chapter still has the
+    /// same images; otherwise the chapter starts over.
+    pub fn start_chapter(&mut self, url: &str, title: &str, position: usize, image_urls: &[String]) {
+        if let Some(entry) = self.chapters.get_mut(url)
+            && entry.images.iter().map(|image| &image.url).eq(image_urls.iter())
+        {
+            entry.title = title.to_string();
+            entry.position = position;
+            return;
+        }
+
+        trace!("Starting journal entry for chapter {}", url);
+        self.chapters.insert(url.to_string(), ChapterEntry {
+            title: title.to_string(),
+            position,
+            state: EntryState::Pending,
+            images: image_urls.iter()
+                .map(|url| ImageEntry { url: url.clone(), state: EntryState::Pending, path: None, size: None })
+                .collect(),
+            spreads: Vec::new(),
+        });
+    }
+
+    /// Pages of a chapter finished by an earlier run that are still intact on disk
+    ///
+    /// A file whose size changed, or that is not a readable image, has to be downloaded again.
+    pub fn completed_pages(&self, url: &str) -> Vec<DownloadedPage> {
+        let Some(entry) = self.chapters.get(url) else {
+            return Vec::new();
+        };
+
+        entry.images.iter().enumerate()
+            .filter(|(_, image)| image.state >= EntryState::Downloaded)
+            .filter_map(|(index, image)| {
+                let path = self.base_dir().join(image.path.as_ref()?);
+                let size = fs::metadata(&path).ok()?.len();
+                if Some(size) != image.size || image_dimensions(&path).is_none() {
+                    debug!("Page {} of {} changed on disk, downloading it again", index + 1, url);
+                    return None;
+                }
+                Some(DownloadedPage { index, url: image.url.clone(), path })
+            })
+            .collect()
+    }
+
+    /// Page images of a chapter in reading order, if every page was downloaded and is still on disk
+    pub fn chapter_pages(&self, url: &str) -> Option<Vec<PathBuf>> {
+        let entry = self.chapters.get(url)?;
+        entry.images.iter()
+            .map(|image| {
+                let path = self.base_dir().join(image.path.as_ref()?);
+                (image.state >= EntryState::Downloaded && path.exists()).then_some(path)
+            })
+            .collect()
+    }
+
+    /// Every chapter of the journal as `(url, entry)` pairs, in reading order
+    pub fn chapters_in_order(&self) -> Vec<(&str, &ChapterEntry)> {
+        let mut chapters = self.chapters.iter()
+            .map(|(url, entry)| (url.as_str(), entry))
+            .collect::<Vec<_>>();
+        chapters.sort_by_key(|(_, entry)| entry.position);
+        chapters
+    }
+
+    /// Record the outcome of downloading a chapter, verifying every downloaded page
+    pub fn record_downloads(&mut self, url: &str, report: &ChapterDownloadReport) {
+        let base_dir = self.base_dir().to_path_buf();
+        let Some(entry) = self.chapters.get_mut(url) else {
+            return;
+        };
+
+        for page in &report.succeeded {
+            let Some(image) = entry.images.get_mut(page.index) else {
+                continue;
+            };
+            image.size = fs::metadata(&page.path).ok().map(|metadata| metadata.len());
+            image.path = Some(page.path.strip_prefix(&base_dir).unwrap_or(&page.path).to_path_buf());
+            image.state = if image_dimensions(&page.path).is_some() {
+                image.state.max(EntryState::Verified)
+            } else {
+                EntryState::Downloaded
+            };
+        }
+        for page in &report.failed {
+            if let Some(image) = entry.images.get_mut(page.index) {
+                image.state = EntryState::Pending;
+            }
+        }
+
+        entry.state = if report.is_complete() {
+            entry.state.max(EntryState::Downloaded)
+        } else {
+            EntryState::Pending
+        };
+    }
+
+    /// Record which pages of a chapter are double-page spreads
+    pub fn record_spreads(&mut self, url: &str, spreads: &[usize]) {
+        if let Some(entry) = self.chapters.get_mut(url) {
+            entry.spreads = spreads.to_vec();
+        }
+    }
+
+    /// Whether `output_path` exists and was built from exactly this content
+    pub fn is_export_current(&self, output_path: &Path, fingerprint: &str) -> bool {
+        output_path.exists()
+            && self.exports.get(&self.export_key(output_path))
+                .is_some_and(|export| export.fingerprint == fingerprint)
+    }
+
+    /// Record that `output_path` was exported with the given chapters
+    pub fn record_export(&mut self, output_path: &Path, fingerprint: &str, chapter_urls: &[&str]) {
+        for url in chapter_urls {
+            if let Some(entry) = self.chapters.get_mut(*url) {
+                entry.state = EntryState::Exported;
+                entry.images.iter_mut()
+                    .filter(|image| image.state == EntryState::Verified)
+                    .for_each(|image| image.state = EntryState::Exported);
+            }
+        }
+
+        self.exports.insert(self.export_key(output_path), ExportEntry {
+            fingerprint: fingerprint.to_string(),
+            chapters: chapter_urls.iter().map(|url| url.to_string()).collect(),
+        });
+    }
+
+    /// Key of an exported file: its path relative to the output directory
+    fn export_key(&self, output_path: &Path) -> String {
+        output_path.strip_prefix(self.base_dir())
+            .unwrap_or(output_path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Fingerprint of an export: a hash of `settings` (anything that changes the output,
+    /// such as the format and its options) and of the content of every page, in order
+    pub fn export_fingerprint(settings: &str, image_paths: &[&Path]) -> Result<String, DownloadError> {
+        let mut input = settings.to_string();
+        for path in image_paths {
+            input.push('\n');
+            input.push_str(&calculate_file_checksum(path)?);
+        }
+        Ok(compute_hash(&input))
+    }
+}
+
+/// Load a JSON state file such as the journal, or start over when there is none yet
+///
+/// A file that can't be parsed is set aside as `<name>.bak` with a warning, so starting
+/// over never loses what it recorded.
+pub(crate) fn load_state_file<T: DeserializeOwned + Default>(path: &Path, name: &str) -> Result<T, DownloadError> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    match serde_json::from_slice(&fs::read(path)?) {
+        Ok(state) => {
+            debug!("Loaded {} from {:?}", name, path);
+            Ok(state)
+        }
+        Err(e) => {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(".bak");
+            warn!("Ignoring unreadable {} {:?}, keeping it as {:?}: {}", name, path, backup, e);
+            fs::rename(path, &backup)?;
+            Ok(T::default())
+        }
+    }
+}
+
+/// Save a JSON state file, replacing the previous version atomically
+pub(crate) fn save_state_file<T: Serialize>(path: &Path, state: &T, name: &str) -> Result<(), DownloadError> {
+    let json = serde_json::to_vec_pretty(state)
+        .map_err(|e| DownloadError::ParsingError(format!("Failed to write {}: {}", name, e)))?;
+    write_file_atomic(path, &json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::downloader::FailedPage;
+
+    fn png(path: &Path) {
+        image::GrayImage::new(4, 6).save(path).unwrap();
+    }
+
+    fn urls() -> Vec<String> {
+        vec![String::from("https://example.com/1.png"), String::from("https://example.com/2.png")]
+    }
+
+    #[test]
+    fn test_journal_resumes_completed_pages() {
+        let dir = std::env::temp_dir().join("manga_downloader_journal_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("chapter-1")).unwrap();
+        let page = dir.join("chapter-1").join("image_0000.png");
+        png(&page);
+
+        let mut journal = Journal::load(&dir).unwrap();
+        journal.start_chapter("https://example.com/c/1", "Chapter 1", 0, &urls());
+        let report = ChapterDownloadReport {
+            succeeded: vec![DownloadedPage { index: 0, url: urls()[0].clone(), path: page.clone() }],
+            failed: vec![FailedPage { index: 1, url: urls()[1].clone(), error: DownloadError::HttpStatus(404, urls()[1].clone()) }],
+            ..Default::default()
+        };
+        journal.record_downloads("https://example.com/c/1", &report);
+        journal.record_spreads("https://example.com/c/1", &[1]);
+        journal.save().unwrap();
+
+        let mut journal = Journal::load(&dir).unwrap();
+        let entry = &journal.chapters["https://example.com/c/1"];
+        assert_eq!(entry.state, EntryState::Pending);
+        assert_eq!(entry.images[0].state, EntryState::Verified);
+        assert_eq!(entry.images[0].path, Some(PathBuf::from("chapter-1/image_0000.png")));
+        assert_eq!(entry.images[1].state, EntryState::Pending);
+        assert_eq!(entry.spreads, vec![1]);
+
+        // Same images: the downloaded page is kept
+        journal.start_chapter("https://example.com/c/1", "Chapter 1", 0, &urls());
+        let completed = journal.completed_pages("https://example.com/c/1");
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].path, page);
+        assert_eq!(journal.chapter_pages("https://example.com/c/1"), None);
+        assert_eq!(journal.chapters_in_order()[0].1.title, "Chapter 1");
+
+        // A page truncated on disk is downloaded again
+        fs::write(&page, b"broken").unwrap();
+        assert!(journal.completed_pages("https://example.com/c/1").is_empty());
+
+        // Different images: the chapter starts over
+        journal.start_chapter("https://example.com/c/1", "Chapter 1", 0, &urls()[..1]);
+        assert_eq!(journal.chapters["https://example.com/c/1"].images[0].state, EntryState::Pending);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_export_fingerprint_tracks_content_and_settings() {
+        let dir = std::env::temp_dir().join("manga_downloader_journal_export_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let page = dir.join("image_0000.png");
+        png(&page);
+        let output = dir.join("chapter-1.pdf");
+        fs::write(&output, b"%PDF-").unwrap();
+
+        let mut journal = Journal::load(&dir).unwrap();
+        let fingerprint = Journal::export_fingerprint("pdf", &[page.as_path()]).unwrap();
+        assert!(!journal.is_export_current(&output, &fingerprint));
+
+        journal.record_export(&output, &fingerprint, &["https://example.com/c/1"]);
+        assert!(journal.is_export_current(&output, &fingerprint));
+        assert_ne!(Journal::export_fingerprint("cbz", &[page.as_path()]).unwrap(), fingerprint);
+
+        image::GrayImage::new(5, 6).save(&page).unwrap();
+        assert_ne!(Journal::export_fingerprint("pdf", &[page.as_path()]).unwrap(), fingerprint);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,13 +1,33 @@
 // Expose modules for integration testing
 pub mod cache;
+pub mod cbz;
+pub mod chapter_number;
 pub mod chapter_to_download;
+pub mod cover;
 pub mod downloader;
+pub mod epub;
 pub mod error;
+pub mod http;
+pub mod journal;
+pub mod library;
 pub mod manga_to_download;
+pub mod manifest;
+pub mod metadata;
+pub mod naming;
 pub mod pdf;
+pub mod placeholder;
+pub mod processing;
+pub mod rate_limit;
+pub mod retry;
+pub mod selection;
+pub mod source;
+pub mod source_profile;
+pub mod volume;
+pub mod webtoon;
 
 // Re-export important types for easier use in tests
 pub use error::DownloadError;
 pub use manga_to_download::MangaToDownload;
 pub use chapter_to_download::ChapterToDownload;
-pub use cache::CacheManager;
\ No newline at end of file
+pub use cache::CacheManager;
+pub use source::{Source, SourceRegistry};
\ No newline at end of file
@@ -0,0 +1,164 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::error::DownloadError;
+use crate::journal::{load_state_file, save_state_file};
+use crate::manga_to_download::ChapterInfo;
+
+/// A series whose new chapters are downloaded by `update`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FollowedSeries {
+    /// Link to the series page
+    pub url: String,
+    /// Title of the series when it was followed
+    pub title: String,
+    /// Directory new chapters are exported to
+    pub output_dir: PathBuf,
+    /// Export format, as given on the command line (e.g. "pdf")
+    pub format: String,
+    /// URLs of the chapters already downloaded or deliberately skipped
+    #[serde(default)]
+    pub seen_chapters: BTreeSet<String>,
+}
+
+impl FollowedSeries {
+    /// Positions in `chapters` of the chapters that haven't been seen yet
+    pub fn new_chapters(&self, chapters: &[ChapterInfo]) -> Vec<usize> {
+        chapters.iter()
+            .filter(|chapter| !self.seen_chapters.contains(&chapter.url))
+            .map(|chapter| chapter.index)
+            .collect()
+    }
+}
+
+/// The followed series, stored as `library.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Library {
+    /// Where the library is saved
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    pub series: Vec<FollowedSeries>,
+}
+
+impl Library {
+    /// Default location of the library: `library.json` in the user's config directory
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .or_else(dirs::home_dir)
+            .unwrap_or_default()
+            .join("download-manga")
+            .join("library.json")
+    }
+
+    /// Load the library at `path`, starting an empty one when the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, DownloadError> {
+        let mut library: Library = load_state_file(path, "library")?;
+        debug!("Loaded library with {} series from {:?}", library.series.len(), path);
+        library.path = path.to_path_buf();
+        Ok(library)
+    }
+
+    /// Save the library, replacing the previous version atomically
+    pub fn save(&self) -> Result<(), DownloadError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        save_state_file(&self.path, self, "library")
+    }
+
+    /// Add a series, or update where and how an already followed one is exported
+    ///
+    /// Returns whether the series is new to the library. Chapters seen earlier are kept.
+    pub fn follow(&mut self, series: FollowedSeries) -> bool {
+        match self.series.iter_mut().find(|followed| followed.url == series.url) {
+            Some(followed) => {
+                followed.title = series.title;
+                followed.output_dir = series.output_dir;
+                followed.format = series.format;
+                followed.seen_chapters.extend(series.seen_chapters);
+                false
+            }
+            None => {
+                self.series.push(series);
+                true
+            }
+        }
+    }
+
+    /// Remove the series with this URL, returning whether it was followed
+    pub fn unfollow(&mut self, url: &str) -> bool {
+        let count = self.series.len();
+        self.series.retain(|series| series.url != url);
+        self.series.len() != count
+    }
+
+    /// Record chapters of the series at `url` as seen
+    pub fn mark_seen(&mut self, url: &str, chapter_urls: impl IntoIterator<Item = String>) {
+        if let Some(series) = self.series.iter_mut().find(|series| series.url == url) {
+            series.seen_chapters.extend(chapter_urls);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(index: usize, url: &str) -> ChapterInfo {
+        ChapterInfo::new(index, format!("Chapter {}", index + 1), url.to_string())
+    }
+
+    fn series(url: &str) -> FollowedSeries {
+        FollowedSeries {
+            url: url.to_string(),
+            title: String::from("Example"),
+            output_dir: PathBuf::from("/tmp/example"),
+            format: String::from("pdf"),
+            seen_chapters: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_library_finds_new_chapters() {
+        let path = std::env::temp_dir().join("manga_downloader_library_test").join("library.json");
+        let _ = fs::remove_file(&path);
+
+        let mut library = Library::load(&path).unwrap();
+        assert!(library.follow(series("https://example.com/manga/example/")));
+        library.mark_seen("https://example.com/manga/example/", [String::from("https://example.com/c/1")]);
+        library.save().unwrap();
+
+        let mut library = Library::load(&path).unwrap();
+        let chapters = [chapter(0, "https://example.com/c/1"), chapter(1, "https://example.com/c/2")];
+        assert_eq!(library.series[0].new_chapters(&chapters), vec![1]);
+
+        // Following again keeps the chapters already seen
+        assert!(!library.follow(series("https://example.com/manga/example/")));
+        assert_eq!(library.series[0].seen_chapters.len(), 1);
+
+        assert!(library.unfollow("https://example.com/manga/example/"));
+        assert!(library.series.is_empty());
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_unreadable_library_is_kept_aside() {
+        let dir = std::env::temp_dir().join("manga_downloader_library_unreadable_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("library.json");
+        fs::write(&path, b"{ not json").unwrap();
+
+        let library = Library::load(&path).unwrap();
+        assert!(library.series.is_empty());
+        assert_eq!(fs::read(dir.join("library.json.bak")).unwrap(), b"{ not json");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,74 +1,571 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use std::io::{self, Write};
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use log::{error, warn, info, debug, trace};
 
-mod manga_to_download;
-mod chapter_to_download;
-mod error;
-mod pdf;
-mod downloader;
-mod cache;
+use download_manga::manga_to_download::{MangaToDownload, ChapterInfo};
+use download_manga::error::DownloadError;
+use download_manga::pdf::{create_volume_pdf, PaperSize, PdfChapter, PdfLayout, PdfMetadata, PdfOptions};
+use download_manga::cbz::{create_cbz_from_images, ComicInfo};
+use download_manga::cover::{download_cover, COVER_FILE_NAME};
+use download_manga::epub::{create_epub, EpubChapter, EpubOptions, EpubSpread};
+use download_manga::volume::{group_chapters, MergeMode};
+use download_manga::webtoon::{reslice_chapter, DEFAULT_PAGE_HEIGHT};
+use download_manga::downloader::{download_remaining_images, ensure_dir_exists, build_chapter_path, partial_path, sanitize_filename, MissingPagePolicy};
+use download_manga::journal::Journal;
+use download_manga::library::{FollowedSeries, Library};
+use download_manga::manifest::{Manifest, ManifestChapter};
+use download_manga::metadata::SeriesMetadata;
+use download_manga::naming::{today, NameTemplate, NameValues};
+use download_manga::cache::CacheManager;
+use download_manga::processing::{process_images, OutputImageFormat, ProcessedChapter, ProcessingOptions, SpreadMode};
+use download_manga::http::{parse_header, HttpClient, HttpConfig, DEFAULT_USER_AGENT};
+use download_manga::rate_limit::RateLimitConfig;
+use download_manga::retry::RetryPolicy;
+use download_manga::selection::ChapterSelector;
+use download_manga::source::{MadaraSource, SourceRegistry};
+use download_manga::source_profile::SourceProfile;
 
-use manga_to_download::{MangaToDownload, ChapterInfo};
-use error::DownloadError;
-use pdf::create_pdf_from_images;
-use downloader::{download_images, ensure_dir_exists, build_chapter_path};
-use cache::CacheManager;
+/// Concurrency used when only the series page is fetched
+const DEFAULT_CONCURRENCY: usize = 5;
 
 /// Download a manga from a given link from https://www.mangaread.org
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = "Download a manga from a given link from https://www.mangaread.org")]
-pub struct Args {
-    /// The link to the manga to download
-    #[arg(short, long)]
-    pub link: String,
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
 
-    /// The output directory
-    #[arg(short, long)]
-    pub output_dir: String,
+    /// Verbose mode (-v for info, -vv for debug, -vvv for trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Download chapters of a series and export them
+    Download {
+        /// The link to the manga to download
+        #[arg(short, long)]
+        link: String,
+
+        /// The output directory
+        #[arg(short, long)]
+        output_dir: String,
+
+        /// Download all chapters without prompting
+        #[arg(short, long)]
+        all: bool,
+
+        /// Chapters to download without prompting, by chapter number, e.g. '12-20,45.5,!15', 'latest:5' or 'since:100'
+        #[arg(long, value_name = "EXPR", conflicts_with = "all", value_parser = parse_chapter_selector)]
+        chapters: Option<ChapterSelector>,
+
+        /// Output format of every chapter
+        #[arg(short, long, value_enum, default_value = "pdf")]
+        format: OutputFormat,
+
+        #[command(flatten)]
+        download: DownloadArgs,
+
+        #[command(flatten)]
+        export: ExportArgs,
+
+        #[command(flatten)]
+        processing: ProcessingArgs,
+
+        #[command(flatten)]
+        network: NetworkArgs,
+
+        #[command(flatten)]
+        cache: CacheArgs,
+    },
+    /// Print the chapters of a series without downloading them
+    List {
+        /// The link to the series
+        #[arg(short, long)]
+        link: String,
+
+        #[command(flatten)]
+        network: NetworkArgs,
+    },
+    /// Print what the series page says about a series, and its chapter count
+    Info {
+        /// The link to the series
+        #[arg(short, long)]
+        link: String,
+
+        #[command(flatten)]
+        network: NetworkArgs,
+    },
+    /// Inspect and maintain the download cache
+    Cache {
+        #[command(flatten)]
+        dir: CacheDirArgs,
+
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Export the chapters downloaded into a directory again, e.g. in another format
+    Export {
+        /// The output directory of an earlier download
+        #[arg(short, long)]
+        output_dir: String,
+
+        /// Output format of every chapter
+        #[arg(short, long, value_enum, default_value = "pdf")]
+        format: OutputFormat,
+
+        #[command(flatten)]
+        export: ExportArgs,
+
+        #[command(flatten)]
+        processing: ProcessingArgs,
+
+        #[command(flatten)]
+        cache: CacheArgs,
+    },
+    /// Add a series to the library, so `update` downloads its new chapters
+    Follow {
+        /// The link to the series
+        #[arg(short, long)]
+        link: String,
 
+        /// The output directory of the series
+        #[arg(short, long)]
+        output_dir: String,
+
+        /// Output format of the series' chapters
+        #[arg(short, long, value_enum, default_value = "pdf")]
+        format: OutputFormat,
+
+        /// Only download chapters released from now on, not the ones already out
+        #[arg(long)]
+        skip_current: bool,
+
+        #[command(flatten)]
+        network: NetworkArgs,
+
+        #[command(flatten)]
+        library: LibraryArgs,
+    },
+    /// Remove a series from the library
+    Unfollow {
+        /// The link to the series
+        #[arg(short, long)]
+        link: String,
+
+        #[command(flatten)]
+        library: LibraryArgs,
+    },
+    /// Download the new chapters of every followed series
+    Update {
+        /// List the new chapters without downloading them
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        library: LibraryArgs,
+
+        #[command(flatten)]
+        download: DownloadArgs,
+
+        #[command(flatten)]
+        export: ExportArgs,
+
+        #[command(flatten)]
+        processing: ProcessingArgs,
+
+        #[command(flatten)]
+        network: NetworkArgs,
+
+        #[command(flatten)]
+        cache: CacheArgs,
+    },
+}
+
+/// Cache maintenance commands
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum CacheAction {
+    /// Print how many chapters and images are cached and their size
+    Stats,
+    /// Check every cached image against its checksum
+    Validate,
+    /// Remove chapters older than the maximum age
+    Clean,
+    /// Remove everything from the cache
+    Clear,
+    /// Drop chapters with missing or corrupted images and delete unknown files
+    Repair,
+}
+
+/// How chapters are downloaded
+#[derive(Debug, clap::Args)]
+pub struct DownloadArgs {
     /// Maximum number of concurrent downloads (default: 5)
     #[arg(short, long, default_value = "5")]
     pub concurrency: usize,
 
-    /// Download all chapters without prompting
-    #[arg(short, long)]
-    pub all: bool,
+    /// What to do with a chapter when some pages fail to download
+    #[arg(long, value_enum, default_value = "fail")]
+    pub on_missing_pages: MissingPagePolicy,
 
-    /// Enable caching of downloaded content
+    /// Skip chapters the manifest of the output directory lists as already exported in the requested format
     #[arg(long)]
-    pub cache: bool,
+    pub skip_existing: bool,
+}
 
-    /// Maximum age of cached content in days (default: 30)
-    #[arg(long, default_value = "30")]
-    pub cache_max_age: u64,
+/// How chapters are exported
+#[derive(Debug, clap::Args)]
+pub struct ExportArgs {
+    /// Combine chapters into one PDF or EPUB per 'volume', per N chapters, or 'all' into one
+    #[arg(long, default_value = "none", value_parser = parse_merge_mode)]
+    pub merge: MergeMode,
 
-    /// Cache directory (default: ~/.manga-cache)
+    /// How images are placed on PDF pages
+    #[arg(long, value_enum, default_value = "fit-page")]
+    pub pdf_layout: PdfLayout,
+
+    /// Paper size of PDF pages for the fit-page and fit-width layouts
+    #[arg(long, value_enum, default_value = "a4")]
+    pub paper_size: PaperSize,
+
+    /// Margin around images in millimetres for the fit-page PDF layout (default: 6)
+    #[arg(long, default_value = "6")]
+    pub margin: f32,
+
+    /// Pages are read left to right instead of right to left (EPUB page order and split spreads)
+    #[arg(long)]
+    pub left_to_right: bool,
+
+    /// When EPUB readers may show two pages side by side
+    #[arg(long, value_enum, default_value = "landscape")]
+    pub epub_spread: EpubSpread,
+
+    /// Add the series cover (cover.jpg in the output directory) as the first PDF page and as the EPUB cover
+    #[arg(long)]
+    pub cover: bool,
+
+    /// Export every selected chapter again, even when it is already exported and unchanged, and download the cover again
+    #[arg(long)]
+    pub force: bool,
+
+    /// Path of exported files in the output directory, e.g. '{series}/Vol.{volume:02}/{series} - Ch.{chapter:04} - {title}.{ext}'
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_name_template)]
+    pub name_template: Option<NameTemplate>,
+}
+
+/// How pages are changed before they are exported
+#[derive(Debug, clap::Args)]
+pub struct ProcessingArgs {
+    /// Stitch webtoon slices into one strip and cut it into pages at the gutters between panels
+    #[arg(long)]
+    pub webtoon: bool,
+
+    /// Target height in pixels of re-sliced webtoon pages (default: 1800)
+    #[arg(long, default_value_t = DEFAULT_PAGE_HEIGHT, value_parser = clap::value_parser!(u32).range(1..))]
+    pub webtoon_page_height: u32,
+
+    /// Convert pages to grayscale, e.g. for e-ink readers
+    #[arg(long)]
+    pub grayscale: bool,
+
+    /// Downscale pages so neither side is larger than this many pixels
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_dimension: Option<u32>,
+
+    /// Re-encode pages as JPEG or lossy WebP (at --quality) before exporting
+    #[arg(long, value_enum, default_value = "keep")]
+    pub image_format: OutputImageFormat,
+
+    /// JPEG and WebP quality from 1 to 100 for re-encoded pages (default: 85)
+    #[arg(long, default_value = "85", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: u8,
+
+    /// Remove plain white or black borders around pages
+    #[arg(long)]
+    pub auto_crop: bool,
+
+    /// Contrast change applied to pages, e.g. 20 for more or -20 for less (default: 0)
+    #[arg(long, default_value = "0", allow_negative_numbers = true)]
+    pub contrast: f32,
+
+    /// Gamma correction of pages, above 1 darkens and below 1 lightens (default: 1)
+    #[arg(long, default_value = "1")]
+    pub gamma: f32,
+
+    /// What to do with double-page spreads: keep, split into two pages, or rotate
+    #[arg(long, value_enum, default_value = "keep")]
+    pub spreads: SpreadMode,
+
+    /// Width to height ratio above which a page is treated as a double-page spread (default: 1.2)
+    #[arg(long, default_value = "1.2")]
+    pub spread_ratio: f32,
+}
+
+impl ProcessingArgs {
+    fn options(&self, right_to_left: bool) -> ProcessingOptions {
+        ProcessingOptions {
+            grayscale: self.grayscale,
+            max_dimension: self.max_dimension,
+            format: self.image_format,
+            quality: self.quality,
+            auto_crop: self.auto_crop,
+            contrast: self.contrast,
+            gamma: self.gamma,
+            spreads: self.spreads,
+            spread_ratio: self.spread_ratio,
+            right_to_left,
+        }
+    }
+}
+
+/// How the manga site is contacted
+#[derive(Debug, clap::Args)]
+pub struct NetworkArgs {
+    /// Selector profile (.toml or .json) for sites whose markup differs from mangaread.org
     #[arg(long)]
+    pub source_profile: Option<String>,
+
+    /// User-Agent sent with every request
+    #[arg(long, default_value = DEFAULT_USER_AGENT)]
+    pub user_agent: String,
+
+    /// Extra header sent with every request, as 'Name: Value' (can be repeated)
+    #[arg(long = "header", value_name = "HEADER")]
+    pub headers: Vec<String>,
+
+    /// Cookie sent to the manga site, as 'name=value' (can be repeated)
+    #[arg(long = "cookie", value_name = "COOKIE")]
+    pub cookies: Vec<String>,
+
+    /// Retries per failed request after the first attempt, 0 to never retry (default: 3)
+    #[arg(long, default_value = "3")]
+    pub retries: u32,
+
+    /// Delay before the first retry in milliseconds, doubled on every attempt (default: 500)
+    #[arg(long, default_value = "500")]
+    pub retry_base_delay: u64,
+
+    /// Maximum delay between retries in milliseconds (default: 30000)
+    #[arg(long, default_value = "30000")]
+    pub retry_max_delay: u64,
+
+    /// Fraction of each retry delay that is randomized, from 0 to 1 (default: 0.25)
+    #[arg(long, default_value = "0.25", value_parser = parse_jitter)]
+    pub retry_jitter: f64,
+
+    /// HTTP statuses that are retried, comma-separated (default: 408,425,429,500,502,503,504)
+    #[arg(long = "retry-status", value_name = "STATUS", value_delimiter = ',', default_value = "408,425,429,500,502,503,504")]
+    pub retry_statuses: Vec<u16>,
+
+    /// Maximum requests per second to a single host, 0 for no limit (default: 4)
+    #[arg(long, default_value = "4", value_parser = parse_rate_limit)]
+    pub rate_limit: f64,
+
+    /// Maximum requests in flight to a single host, 0 for no limit (default: 4)
+    #[arg(long, default_value = "4")]
+    pub max_per_host: usize,
+}
+
+/// Where the cache is
+#[derive(Debug, clap::Args)]
+pub struct CacheDirArgs {
+    /// Cache directory (default: ~/.manga-cache)
+    #[arg(long, global = true)]
     pub cache_dir: Option<String>,
 
-    /// Validate cache integrity
+    /// Maximum age of cached content in days (default: 30)
+    #[arg(long, default_value = "30", global = true)]
+    pub cache_max_age: u64,
+}
+
+impl CacheDirArgs {
+    fn open(&self) -> Result<CacheManager, DownloadError> {
+        let cache_dir = self.cache_dir.as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                dirs::home_dir()
+                    .expect("Failed to determine home directory")
+                    .join(".manga-cache")
+            });
+
+        debug!("Using cache directory: {:?}", cache_dir);
+        CacheManager::new(&cache_dir, self.cache_max_age)
+    }
+}
+
+/// Whether and where downloads are cached
+#[derive(Debug, clap::Args)]
+pub struct CacheArgs {
+    /// Enable caching of downloaded content
     #[arg(long)]
-    pub validate_cache: bool,
+    pub cache: bool,
+
+    #[command(flatten)]
+    pub dir: CacheDirArgs,
+}
+
+impl CacheArgs {
+    fn open(&self) -> Result<Option<CacheManager>, DownloadError> {
+        if self.cache {
+            info!("Initializing cache manager");
+            self.dir.open().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
 
-    /// Clear the cache
+/// Where the followed series are kept
+#[derive(Debug, clap::Args)]
+pub struct LibraryArgs {
+    /// Library of followed series (default: download-manga/library.json in the config directory)
     #[arg(long)]
-    pub clear_cache: bool,
+    pub library: Option<String>,
+}
 
-    /// Verbose mode (-v for info, -vv for debug, -vvv for trace)
-    #[arg(short, long, action = clap::ArgAction::Count)]
-    pub verbose: u8,
+impl LibraryArgs {
+    fn path(&self) -> PathBuf {
+        self.library.as_ref().map(PathBuf::from).unwrap_or_else(Library::default_path)
+    }
+}
+
+fn parse_jitter(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(jitter) if (0.0..=1.0).contains(&jitter) => Ok(jitter),
+        _ => Err(format!("'{}' is not a number from 0 to 1", value)),
+    }
+}
+
+fn parse_rate_limit(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate >= 0.0 => Ok(rate),
+        _ => Err(format!("'{}' is not a number of requests per second of 0 or more", value)),
+    }
+}
+
+fn parse_merge_mode(value: &str) -> Result<MergeMode, String> {
+    value.parse().map_err(|e: DownloadError| e.to_string())
+}
+
+fn parse_chapter_selector(value: &str) -> Result<ChapterSelector, String> {
+    value.parse().map_err(|e: DownloadError| e.to_string())
+}
+
+fn parse_name_template(value: &str) -> Result<NameTemplate, String> {
+    value.parse().map_err(|e: DownloadError| e.to_string())
+}
+
+/// A downloaded chapter waiting to be exported
+struct ExportChapter {
+    title: String,
+    url: String,
+    image_paths: Vec<PathBuf>,
+}
+
+/// File format chapters are exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// One PDF per chapter
+    Pdf,
+    /// One comic book archive per chapter, with ComicInfo.xml metadata
+    Cbz,
+    /// One fixed-layout EPUB 3 per chapter
+    Epub,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Cbz => "cbz",
+            OutputFormat::Epub => "epub",
+        }
+    }
+}
+
+/// Which chapters of a series to download
+enum ChapterSelection {
+    /// Every chapter (--all)
+    All,
+    /// Ask the user
+    Prompt,
+    /// Chapters matching a --chapters expression
+    Expression(ChapterSelector),
+    /// Chapters of a followed series that haven't been seen yet
+    New(FollowedSeries),
+}
+
+/// Where and in which format a series is downloaded
+struct DownloadTarget {
+    link: String,
+    output_dir: PathBuf,
+    format: OutputFormat,
+}
+
+/// State shared by every series downloaded in one run
+struct Session {
+    registry: SourceRegistry,
+    client: HttpClient,
+    cookies: Vec<String>,
+    cache_manager: Option<CacheManager>,
+}
+
+impl Session {
+    fn new(network: &NetworkArgs, cache_manager: Option<CacheManager>) -> Result<Self, DownloadError> {
+        // Pick the scraper for the site the link points to
+        let mut registry = SourceRegistry::default();
+        if let Some(ref profile_path) = network.source_profile {
+            let profile = SourceProfile::from_file(profile_path)?;
+            info!("Loaded source profile '{}' from {}", profile.name, profile_path);
+            registry.register_preferred(Arc::new(MadaraSource::from_profile(profile)?));
+        }
+        // One client is shared by every request so connections and cookies are reused
+        let http_config = HttpConfig {
+            user_agent: network.user_agent.clone(),
+            headers: network.headers.iter()
+                .map(|header| parse_header(header))
+                .collect::<Result<Vec<_>, _>>()?,
+            retry: RetryPolicy {
+                max_attempts: network.retries.saturating_add(1),
+                base_delay: Duration::from_millis(network.retry_base_delay),
+                max_delay: Duration::from_millis(network.retry_max_delay),
+                jitter: network.retry_jitter,
+                retryable_statuses: network.retry_statuses.clone(),
+            },
+            rate_limit: RateLimitConfig {
+                requests_per_second: network.rate_limit,
+                max_in_flight: network.max_per_host,
+                ..RateLimitConfig::default()
+            },
+            ..HttpConfig::default()
+        };
+        let client = HttpClient::new(&http_config)?;
+        Ok(Session { registry, client, cookies: network.cookies.clone(), cache_manager })
+    }
+
+    /// Load the series page at `link` with the source for its site
+    async fn load_manga(&self, link: &str, concurrency: usize) -> Result<MangaToDownload, DownloadError> {
+        let source = self.registry.source_for(link)?;
+        info!("Using source: {}", source.name());
+        for cookie in &self.cookies {
+            self.client.add_cookie(cookie, link)?;
+        }
+        MangaToDownload::with_source(link.to_string(), concurrency, source, self.client.clone()).await
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), DownloadError> {
-    let args = Args::parse();
+    let cli = Cli::parse();
 
     // Initialize logger with appropriate verbosity level
     let env = env_logger::Env::default()
-        .filter_or("RUST_LOG", match args.verbose {
+        .filter_or("RUST_LOG", match cli.verbose {
             0 => "warn",
             1 => "info",
             2 => "debug",
@@ -81,56 +578,225 @@ async fn main() -> Result<(), DownloadError> {
         .init();
 
     info!("Starting manga downloader");
-    debug!("Command line arguments: {:?}", args);
-
-    // Set up cache if enabled
-    let cache_dir = args.cache_dir.clone()
-        .map(|dir| Path::new(&dir).to_path_buf())
-        .unwrap_or_else(|| {
-            dirs::home_dir()
-                .expect("Failed to determine home directory")
-                .join(".manga-cache")
-        });
-
-    debug!("Using cache directory: {:?}", cache_dir);
-
-    // Initialize cache manager if caching is enabled
-    let mut cache_manager = if args.cache || args.validate_cache || args.clear_cache {
-        info!("Initializing cache manager");
-        Some(CacheManager::new(&cache_dir, args.cache_max_age)?)
-    } else {
-        None
-    };
+    debug!("Command line arguments: {:?}", cli);
 
-    // Handle cache management commands
-    if args.clear_cache {
-        if let Some(ref mut cache) = cache_manager {
-            info!("Clearing cache...");
-            cache.clear_cache()?;
-            info!("Cache cleared successfully.");
+    match cli.command {
+        Command::Download { link, output_dir, all, chapters, format, download, export, processing, network, cache } => {
+            let mut session = Session::new(&network, cache.open()?)?;
+            let target = DownloadTarget { link, output_dir: PathBuf::from(output_dir), format };
+            let selection = match chapters {
+                Some(selector) => ChapterSelection::Expression(selector),
+                None if all => ChapterSelection::All,
+                None => ChapterSelection::Prompt,
+            };
+            download_series(&mut session, &target, selection, &download, &export, &processing).await?;
+            Ok(())
+        }
+        Command::List { link, network } => {
+            let session = Session::new(&network, None)?;
+            let manga = session.load_manga(&link, DEFAULT_CONCURRENCY).await?;
+            for chapter in manga.list_available_chapters()? {
+                println!("[{}] {}", chapter.index, chapter.title);
+            }
+            Ok(())
+        }
+        Command::Info { link, network } => {
+            let session = Session::new(&network, None)?;
+            let manga = session.load_manga(&link, DEFAULT_CONCURRENCY).await?;
+            let chapters = manga.list_available_chapters()?;
+            print_series_info(&manga.metadata, manga.source.name(), chapters.len());
+            Ok(())
         }
+        Command::Cache { dir, action } => manage_cache(&dir, action),
+        Command::Export { output_dir, format, export, processing, cache } => {
+            let cache_manager = cache.open()?;
+            export_directory(Path::new(&output_dir), format, &export, &processing, cache_manager.as_ref()).await
+        }
+        Command::Follow { link, output_dir, format, skip_current, network, library } => {
+            let session = Session::new(&network, None)?;
+            follow_series(&session, &link, &output_dir, format, skip_current, &library.path()).await
+        }
+        Command::Unfollow { link, library } => {
+            let mut library = Library::load(&library.path())?;
+            if library.unfollow(&link) {
+                library.save()?;
+                println!("No longer following {}", link);
+            } else {
+                println!("{} is not followed", link);
+            }
+            Ok(())
+        }
+        Command::Update { dry_run, library, download, export, processing, network, cache } => {
+            let mut session = Session::new(&network, cache.open()?)?;
+            update_library(&mut session, &library.path(), dry_run, &download, &export, &processing).await
+        }
+    }
+}
 
-        if !args.cache && !args.validate_cache {
-            return Ok(());
+/// Print the details of a series for the `info` command, leaving out what the site doesn't say
+fn print_series_info(metadata: &SeriesMetadata, source: &str, chapter_count: usize) {
+    let list = |values: &[String]| (!values.is_empty()).then(|| values.join(", "));
+    let fields = [
+        ("Title", Some(metadata.title.clone())),
+        ("Also known as", list(&metadata.alternative_titles)),
+        ("Link", Some(metadata.url.clone())),
+        ("Source", Some(source.to_string())),
+        ("Authors", list(&metadata.authors)),
+        ("Artists", list(&metadata.artists)),
+        ("Genres", list(&metadata.genres)),
+        ("Tags", list(&metadata.tags)),
+        ("Status", metadata.status.map(|status| status.as_str().to_string())),
+        ("Released", metadata.release_year.map(|year| year.to_string())),
+        ("Rating", metadata.rating.map(|rating| rating.to_string())),
+        ("Cover", metadata.cover_url.clone()),
+        ("Chapters", Some(chapter_count.to_string())),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            println!("{:<14} {}", format!("{}:", name), value);
         }
     }
+    if let Some(ref description) = metadata.description {
+        println!("\n{}", description);
+    }
+}
 
-    if args.validate_cache {
-        if let Some(ref cache) = cache_manager {
+/// Run a cache maintenance command
+fn manage_cache(dir: &CacheDirArgs, action: CacheAction) -> Result<(), DownloadError> {
+    let mut cache = dir.open()?;
+    match action {
+        CacheAction::Stats => {
+            let stats = cache.stats();
+            println!("Chapters: {} ({} expired)", stats.chapters, stats.expired_chapters);
+            println!("Images:   {}", stats.images);
+            println!("Size:     {:.1} MiB", stats.total_size as f64 / (1024.0 * 1024.0));
+        }
+        CacheAction::Validate => {
             info!("Validating cache...");
             let (valid, invalid) = cache.validate_cache()?;
-            info!("Cache validation complete: {} valid items, {} invalid items", valid, invalid);
+            println!("{} valid items, {} invalid items", valid, invalid);
             if invalid > 0 {
-                warn!("Cache contains {} invalid items", invalid);
+                warn!("Cache contains {} invalid items, run `cache repair` to remove them", invalid);
+            }
+        }
+        CacheAction::Clean => {
+            let removed = cache.clean_expired()?;
+            println!("Removed {} expired chapters", removed);
+        }
+        CacheAction::Clear => {
+            info!("Clearing cache...");
+            cache.clear_cache()?;
+            println!("Cache cleared");
+        }
+        CacheAction::Repair => {
+            let (chapters, files) = cache.repair()?;
+            println!("Dropped {} broken chapters and deleted {} unknown files", chapters, files);
+        }
+    }
+    Ok(())
+}
+
+/// Add a series to the library
+async fn follow_series(session: &Session, link: &str, output_dir: &str, format: OutputFormat, skip_current: bool, library_path: &Path) -> Result<(), DownloadError> {
+    let manga = session.load_manga(link, DEFAULT_CONCURRENCY).await?;
+
+    let seen_chapters = if skip_current {
+        manga.list_available_chapters()?.into_iter().map(|chapter| chapter.url).collect()
+    } else {
+        Default::default()
+    };
+    let series = FollowedSeries {
+        url: link.to_string(),
+        title: manga.get_title(),
+        output_dir: PathBuf::from(output_dir),
+        format: format.extension().to_string(),
+        seen_chapters,
+    };
+
+    let mut library = Library::load(library_path)?;
+    if library.follow(series) {
+        println!("Following {}", manga.get_title());
+    } else {
+        println!("Updated {} in the library", manga.get_title());
+    }
+    library.save()
+}
+
+/// Download the chapters of every followed series that haven't been seen yet
+async fn update_library(session: &mut Session, library_path: &Path, dry_run: bool, download: &DownloadArgs, export: &ExportArgs, processing: &ProcessingArgs) -> Result<(), DownloadError> {
+    let mut library = Library::load(library_path)?;
+    if library.series.is_empty() {
+        println!("No series followed yet, add one with the follow command");
+        return Ok(());
+    }
+
+    if dry_run {
+        for (title, new_chapters) in preview_updates(session, &library, download.concurrency).await {
+            println!("{}: {} new chapters", title, new_chapters.len());
+            for chapter in new_chapters {
+                println!("  {}", chapter);
             }
         }
+        return Ok(());
+    }
+
+    for series in library.series.clone() {
+        info!("Checking {} for new chapters", series.title);
+        let format = match OutputFormat::from_str(&series.format, true) {
+            Ok(format) => format,
+            Err(e) => {
+                error!("Skipping {}: invalid format '{}': {}", series.title, series.format, e);
+                continue;
+            }
+        };
 
-        if !args.cache {
-            return Ok(());
+        let target = DownloadTarget { link: series.url.clone(), output_dir: series.output_dir.clone(), format };
+        match download_series(session, &target, ChapterSelection::New(series.clone()), download, export, processing).await {
+            Ok(done) => {
+                println!("{}: {} new chapters downloaded", series.title, done.len());
+                library.mark_seen(&series.url, done);
+                library.save()?;
+            }
+            Err(e) => error!("Failed to update {}: {}", series.title, e),
         }
     }
 
-    let mut manga = MangaToDownload::new(args.link.clone(), args.concurrency).await?;
+    Ok(())
+}
+
+/// Titles of the new chapters of every followed series, by series title, without downloading anything
+///
+/// A series that can't be checked is logged and left out, so it doesn't stop the others.
+async fn preview_updates(session: &Session, library: &Library, concurrency: usize) -> Vec<(String, Vec<String>)> {
+    let mut previews = Vec::new();
+    for series in &library.series {
+        info!("Checking {} for new chapters", series.title);
+        let chapters = session.load_manga(&series.url, concurrency).await
+            .and_then(|manga| manga.list_available_chapters());
+        let chapters = match chapters {
+            Ok(chapters) => chapters,
+            Err(e) => {
+                error!("Failed to check {}: {}", series.title, e);
+                continue;
+            }
+        };
+
+        let new_chapters = series.new_chapters(&chapters).into_iter()
+            .map(|i| chapters[i].title.clone())
+            .collect();
+        previews.push((series.title.clone(), new_chapters));
+    }
+    previews
+}
+
+/// Download the selected chapters of a series and export them
+///
+/// Returns the URLs of the selected chapters that are now exported, including the ones
+/// that already were.
+async fn download_series(session: &mut Session, target: &DownloadTarget, selection: ChapterSelection, download: &DownloadArgs, export: &ExportArgs, processing: &ProcessingArgs) -> Result<Vec<String>, DownloadError> {
+    let mut manga = session.load_manga(&target.link, download.concurrency).await?;
+    let client = session.client.clone();
+    let cache_manager = &mut session.cache_manager;
     let title = manga.get_title();
 
     info!("Manga: {}", title);
@@ -140,35 +806,85 @@ async fn main() -> Result<(), DownloadError> {
     debug!("Found {} chapters", chapters.len());
 
     // Select which chapters to download
-    let selected_indices = if args.all {
-        // If --all flag is set, download all chapters
-        info!("Downloading all {} chapters", chapters.len());
-        (0..chapters.len()).collect::<Vec<_>>()
-    } else {
+    let selected_indices = match selection {
+        ChapterSelection::All => {
+            // If --all flag is set, download all chapters
+            info!("Downloading all {} chapters", chapters.len());
+            (0..chapters.len()).collect::<Vec<_>>()
+        }
         // Otherwise, let the user select chapters
-        select_chapters(&chapters)?
+        ChapterSelection::Prompt => select_chapters(&chapters)?,
+        ChapterSelection::Expression(selector) => selector.resolve(&chapters)?,
+        ChapterSelection::New(series) => series.new_chapters(&chapters),
     };
 
     info!("Selected {} chapters for download", selected_indices.len());
 
-    // Download selected chapters
-    manga.download_chapters(&selected_indices).await?;
+    // Chapters already exported into the output directory, by chapter URL
+    let mut manifest = Manifest::load(&target.output_dir)?;
+    let mut done = Vec::new();
+    let selected_indices = if download.skip_existing && !export.force {
+        if export.merge == MergeMode::None {
+            let (exported, remaining): (Vec<usize>, Vec<usize>) = selected_indices.into_iter()
+                .partition(|&i| chapters.get(i).is_some_and(|chapter| manifest.is_exported(&chapter.url, target.format.extension())));
+            info!("Skipping {} chapters that are already exported", exported.len());
+            done.extend(exported.into_iter().map(|i| chapters[i].url.clone()));
+            remaining
+        } else {
+            // A merged file mixing old and new chapters must be rebuilt as a whole
+            warn!("--skip-existing is ignored with --merge; unchanged merged files are still not rebuilt");
+            selected_indices
+        }
+    } else {
+        selected_indices
+    };
 
     // Create output directory
-    let output_dir = Path::new(&args.output_dir);
+    let output_dir = target.output_dir.as_path();
     ensure_dir_exists(output_dir)?;
     debug!("Created output directory: {:?}", output_dir);
 
+    // Saved for other tools and for exporting the directory again later, also when no
+    // chapter is new, so a nightly update still fills in a missing cover
+    if let Err(e) = manga.metadata.save(output_dir) {
+        warn!("Failed to save series metadata: {}", e);
+    }
+    if let Some(ref cover_url) = manga.metadata.cover_url {
+        let cover_path = output_dir.join(COVER_FILE_NAME);
+        if cover_path.exists() && !export.force {
+            debug!("Keeping the existing cover {:?}", cover_path);
+        } else if let Err(e) = download_cover(&client, cover_url, &target.link, output_dir).await {
+            warn!("Failed to download the cover: {}", e);
+        }
+    }
+
+    if selected_indices.is_empty() {
+        println!("No chapters left to download for {}", title);
+        return Ok(done);
+    }
+
+    // Download selected chapters
+    manga.download_chapters(&selected_indices).await?;
+
+    // Progress of earlier runs into the same directory, so interrupted runs resume where they stopped
+    let mut journal = Journal::load(output_dir)?;
+    journal.title = Some(title.clone());
+
     // Process downloaded chapters
+    let mut ready_chapters = Vec::new();
     for chapter in manga.chapters {
         info!("Processing chapter: {}", chapter.title);
         debug!("Chapter URL: {}", chapter.url);
 
+        // The journal lists every chapter of the directory, so `export` can find it later
+        let position = chapters.iter().position(|info| info.url == chapter.url).unwrap_or_default();
+        journal.start_chapter(&chapter.url, &chapter.title, position, &chapter.images);
+
         // Check cache first if caching is enabled
         let mut use_cached_images = false;
         let mut cached_image_paths = Vec::new();
 
-        if let Some(ref cache) = cache_manager {
+        if let Some(ref cache) = *cache_manager {
             if cache.is_chapter_cached(&chapter.url) {
                 info!("Using cached version of chapter: {}", chapter.title);
                 if let Some(paths) = cache.get_cached_image_paths(&chapter.url) {
@@ -183,40 +899,59 @@ async fn main() -> Result<(), DownloadError> {
             }
         }
 
-        let image_paths = if use_cached_images {
-            cached_image_paths
+        let (image_paths, mut report) = if use_cached_images {
+            (cached_image_paths, None)
         } else {
             // Create chapter directory
             let chapter_dir = build_chapter_path(output_dir, &chapter.title);
             ensure_dir_exists(&chapter_dir)?;
             debug!("Created chapter directory: {:?}", chapter_dir);
 
-            // Download images
-            info!("Downloading {} images for chapter: {}", chapter.images.len(), chapter.title);
-            let downloaded_paths = download_images(chapter.images.clone(), &chapter_dir, args.concurrency).await;
-            debug!("Downloaded {} images", downloaded_paths.len());
-
-            // Cache the downloaded images if caching is enabled
-            if let Some(ref mut cache) = cache_manager {
-                debug!("Caching chapter metadata and images");
-                // Cache chapter metadata
-                cache.cache_chapter(&chapter.url, &chapter.title, &chapter.images)?;
-
-                // Cache each downloaded image
-                for (i, path) in downloaded_paths.iter().enumerate() {
-                    if i < chapter.images.len() {
-                        let image_url = &chapter.images[i];
-                        match cache.cache_image(&chapter.url, image_url, path) {
-                            Ok(_) => trace!("Cached image: {}", image_url),
-                            Err(e) => warn!("Failed to cache image {}: {}", image_url, e),
+            // Download images, skipping pages an interrupted run already finished
+            let completed = journal.completed_pages(&chapter.url);
+            if !completed.is_empty() {
+                info!("Resuming chapter {}: {} of {} images already downloaded", chapter.title, completed.len(), chapter.images.len());
+            }
+            info!("Downloading {} images for chapter: {}", chapter.images.len() - completed.len(), chapter.title);
+            let report = download_remaining_images(&client, chapter.images.clone(), Some(&chapter.url), &chapter_dir, download.concurrency, completed).await;
+
+            journal.record_downloads(&chapter.url, &report);
+            if let Err(e) = journal.save() {
+                warn!("Failed to save download journal: {}", e);
+            }
+
+            for page in &report.failed {
+                warn!("Page {} of chapter {} failed ({}): {}", page.index + 1, chapter.title, page.url, page.error);
+            }
+
+            // Only complete chapters are cached, so a later run retries the missing pages
+            if let Some(ref mut cache) = *cache_manager {
+                if report.is_complete() {
+                    debug!("Caching chapter metadata and images");
+                    // Cache chapter metadata
+                    cache.cache_chapter(&chapter.url, &chapter.title, &chapter.images)?;
+
+                    // Cache each downloaded image
+                    for page in &report.succeeded {
+                        match cache.cache_image(&chapter.url, &page.url, &page.path) {
+                            Ok(_) => trace!("Cached image: {}", page.url),
+                            Err(e) => warn!("Failed to cache image {}: {}", page.url, e),
                         }
                     }
-                }
 
-                info!("Chapter cached successfully");
+                    info!("Chapter cached successfully");
+                } else {
+                    debug!("Not caching incomplete chapter: {}", chapter.title);
+                }
             }
 
-            downloaded_paths
+            match report.pages_for_export(download.on_missing_pages, &chapter_dir) {
+                Ok(paths) => (paths, Some(report)),
+                Err(e) => {
+                    error!("Skipping chapter {}: {}", chapter.title, e);
+                    continue;
+                }
+            }
         };
 
         if image_paths.is_empty() {
@@ -224,23 +959,294 @@ async fn main() -> Result<(), DownloadError> {
             continue;
         }
 
-        // Create PDF
-        let chapter_slug = chapter.title.replace(" ", "-").to_lowercase();
-        info!("Creating PDF for chapter: {}", chapter.title);
-        let pdf_path = output_dir.join(format!("{}.pdf", chapter_slug));
-        debug!("PDF path: {:?}", pdf_path);
-
-        match create_pdf_from_images(&image_paths, &pdf_path) {
-            Ok(_) => info!("✓ PDF created successfully"),
-            Err(e) => error!("✗ Failed to create PDF: {}", e),
+        let chapter_dir = build_chapter_path(output_dir, &chapter.title);
+        let processed = match finish_pages(&chapter.url, image_paths, &chapter_dir, processing, !export.left_to_right, &mut journal).await {
+            Ok(processed) => processed,
+            Err(e) => {
+                error!("Skipping chapter {}: {}", chapter.title, e);
+                continue;
+            }
+        };
+        match report.as_mut() {
+            Some(report) => {
+                report.spreads = processed.spreads;
+                info!("Chapter {}: {}", chapter.title, report.summary());
+            }
+            None => log_spreads(&chapter.title, &processed.spreads),
         }
+
+        ready_chapters.push(ExportChapter {
+            title: chapter.title,
+            url: chapter.url,
+            image_paths: processed.image_paths,
+        });
     }
 
+    done.extend(export_chapters(target, export, &manga.metadata, &ready_chapters, &mut journal, &mut manifest));
+
     info!("All chapters have been processed");
 
+    Ok(done)
+}
+
+/// Export the chapters downloaded into `output_dir` again, from the journal of the directory
+///
+/// Pages that are no longer on disk are taken from the cache when it is enabled.
+async fn export_directory(output_dir: &Path, format: OutputFormat, export: &ExportArgs, processing: &ProcessingArgs, cache_manager: Option<&CacheManager>) -> Result<(), DownloadError> {
+    let mut journal = Journal::load(output_dir)?;
+    if journal.chapters.is_empty() {
+        println!("Nothing to export: no chapters were downloaded into {}", output_dir.display());
+        return Ok(());
+    }
+
+    // Series details come from series.json; directories from older versions only know the title
+    let mut series = SeriesMetadata::load(output_dir).unwrap_or_default();
+    if series.title.is_empty() {
+        series.title = journal.title.clone()
+            .or_else(|| output_dir.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+    }
+    let entries = journal.chapters_in_order().into_iter()
+        .map(|(url, entry)| (url.to_string(), entry.title.clone()))
+        .collect::<Vec<_>>();
+
+    let mut ready_chapters = Vec::new();
+    for (url, chapter_title) in entries {
+        let pages = journal.chapter_pages(&url).or_else(|| {
+            cache_manager
+                .filter(|cache| cache.is_chapter_cached(&url))
+                .and_then(|cache| cache.get_cached_image_paths(&url))
+        });
+        let Some(pages) = pages else {
+            warn!("Skipping chapter {}: its pages are neither on disk nor in the cache", chapter_title);
+            continue;
+        };
+
+        let chapter_dir = build_chapter_path(output_dir, &chapter_title);
+        match finish_pages(&url, pages, &chapter_dir, processing, !export.left_to_right, &mut journal).await {
+            Ok(processed) => {
+                log_spreads(&chapter_title, &processed.spreads);
+                ready_chapters.push(ExportChapter { title: chapter_title, url, image_paths: processed.image_paths });
+            }
+            Err(e) => error!("Skipping chapter {}: {}", chapter_title, e),
+        }
+    }
+
+    let target = DownloadTarget { link: String::new(), output_dir: output_dir.to_path_buf(), format };
+    let mut manifest = Manifest::load(output_dir)?;
+    let exported = export_chapters(&target, export, &series, &ready_chapters, &mut journal, &mut manifest);
+    println!("{} of {} chapters exported", exported.len(), journal.chapters.len());
     Ok(())
 }
 
+/// Re-slice and process the pages of a chapter as requested, returning the pages to export
+///
+/// Derived pages go to their own directories so downloaded and cached images stay untouched.
+/// The double-page spreads found are recorded in the journal entry of the chapter.
+async fn finish_pages(url: &str, image_paths: Vec<PathBuf>, chapter_dir: &Path, processing: &ProcessingArgs, right_to_left: bool, journal: &mut Journal) -> Result<ProcessedChapter, DownloadError> {
+    let image_paths = if processing.webtoon {
+        reslice_chapter(image_paths, &chapter_dir.join("webtoon"), processing.webtoon_page_height, processing.quality).await?
+    } else {
+        image_paths
+    };
+
+    let processed = process_images(image_paths, &chapter_dir.join("processed"), &processing.options(right_to_left)).await?;
+    journal.record_spreads(url, &processed.spreads);
+    Ok(processed)
+}
+
+/// Log the double-page spreads of a chapter whose pages were not downloaded in this run
+fn log_spreads(chapter_title: &str, spreads: &[usize]) {
+    if !spreads.is_empty() {
+        let pages = spreads.iter().map(|i| (i + 1).to_string()).collect::<Vec<_>>();
+        info!("Chapter {} has double-page spreads on pages {}", chapter_title, pages.join(", "));
+    }
+}
+
+/// Export chapters in reading order, recording every written file in the journal and manifest
+///
+/// Files whose content and settings haven't changed since the last export are skipped
+/// unless --force is given. Returns the URLs of the chapters that are now exported.
+fn export_chapters(target: &DownloadTarget, export: &ExportArgs, series: &SeriesMetadata, ready_chapters: &[ExportChapter], journal: &mut Journal, manifest: &mut Manifest) -> Vec<String> {
+    let output_dir = target.output_dir.as_path();
+    let title = &series.title;
+    let mut done = Vec::new();
+
+    // The cover saved with the series; CBZ readers take it from the directory themselves
+    let cover = (export.cover && target.format != OutputFormat::Cbz).then(|| output_dir.join(COVER_FILE_NAME)).filter(|cover| {
+        let exists = cover.is_file();
+        if !exists {
+            warn!("No {} in {}, exporting without a cover", COVER_FILE_NAME, output_dir.display());
+        }
+        exists
+    });
+
+    let pdf_options = PdfOptions {
+        layout: export.pdf_layout,
+        paper_size: export.paper_size,
+        margin_mm: export.margin,
+        cover: cover.clone(),
+    };
+
+    // Chapters are exported once all of them are downloaded, so they can be merged
+    let merge = if target.format == OutputFormat::Cbz && export.merge != MergeMode::None {
+        warn!("Merging chapters is not supported for CBZ, writing one archive per chapter");
+        MergeMode::None
+    } else {
+        export.merge
+    };
+    let chapter_infos = ready_chapters.iter().enumerate()
+        .map(|(i, chapter)| ChapterInfo::new(i, chapter.title.clone(), chapter.url.clone()))
+        .collect::<Vec<_>>();
+    // Everything besides the pages themselves that changes the exported files
+    let export_settings = format!(
+        "{:?} {:?} {:?} {:?} {}",
+        target.format, merge, pdf_options, export.epub_spread, export.left_to_right
+    );
+
+    for group in group_chapters(&chapter_infos, merge) {
+        let chapters = group.chapters.iter().map(|&i| &ready_chapters[i]).collect::<Vec<_>>();
+        let first = chapters[0];
+        let last = chapters[chapters.len() - 1];
+        let first_info = &chapter_infos[group.chapters[0]];
+
+        let file_stem = if merge == MergeMode::None {
+            chapter_file_stem(first_info)
+        } else {
+            sanitize_filename(&format!("{} {}", title, group.label))
+        };
+        info!("Creating {} for: {}", target.format.extension().to_uppercase(), group.label);
+        let output_path = match export.name_template {
+            Some(ref template) => {
+                let number = &first_info.number;
+                output_dir.join(template.render(&NameValues {
+                    series: title.clone(),
+                    title: if chapters.len() == 1 { first.title.clone() } else { group.label.clone() },
+                    chapter: number.chapter,
+                    volume: number.volume,
+                    host: reqwest::Url::parse(&first.url).ok()
+                        .and_then(|url| url.host_str().map(str::to_string))
+                        .unwrap_or_default(),
+                    date: today(),
+                    pages: chapters.iter().map(|chapter| chapter.image_paths.len()).sum(),
+                    ext: target.format.extension().to_string(),
+                }))
+            }
+            None => output_dir.join(format!("{}.{}", file_stem, target.format.extension())),
+        };
+        debug!("Output path: {:?}", output_path);
+        if let Some(parent) = output_path.parent()
+            && let Err(e) = ensure_dir_exists(parent)
+        {
+            error!("✗ Failed to create directory for {}: {}", group.label, e);
+            continue;
+        }
+
+        let document_title = if merge == MergeMode::None {
+            format!("{} - {}", title, first.title)
+        } else {
+            format!("{} - {}", title, group.label)
+        };
+
+        let image_paths = cover.iter().map(PathBuf::as_path)
+            .chain(chapters.iter().flat_map(|chapter| chapter.image_paths.iter().map(PathBuf::as_path)))
+            .collect::<Vec<_>>();
+        let chapter_titles = chapters.iter().map(|chapter| chapter.title.as_str()).collect::<Vec<_>>();
+        let fingerprint = match Journal::export_fingerprint(&format!("{} {} {:?}", export_settings, document_title, chapter_titles), &image_paths) {
+            Ok(fingerprint) => fingerprint,
+            Err(e) => {
+                error!("✗ Failed to read pages of {}: {}", group.label, e);
+                continue;
+            }
+        };
+        if !export.force && journal.is_export_current(&output_path, &fingerprint) {
+            info!("{} is up to date, skipping", output_path.display());
+            done.extend(chapters.iter().map(|chapter| chapter.url.clone()));
+            continue;
+        }
+
+        // Exports are written under a temporary name, so an interrupted run never leaves a
+        // truncated file that looks complete
+        let partial_output_path = partial_path(&output_path);
+        let result = match target.format {
+            OutputFormat::Pdf => {
+                let metadata = PdfMetadata {
+                    title: Some(document_title),
+                    author: (!series.authors.is_empty()).then(|| series.authors.join(", ")),
+                    subject: Some(if chapters.len() == 1 {
+                        first.title.clone()
+                    } else {
+                        format!("{} chapters: {} to {}", chapters.len(), first.title, last.title)
+                    }),
+                    keywords: series.genres.clone(),
+                };
+                let pdf_chapters = chapters.iter()
+                    .map(|chapter| PdfChapter { title: chapter.title.clone(), image_paths: chapter.image_paths.clone() })
+                    .collect::<Vec<_>>();
+                create_volume_pdf(&pdf_chapters, &partial_output_path, &pdf_options, &metadata)
+            }
+            OutputFormat::Cbz => {
+                let info = ComicInfo::for_chapter(title, first_info, first.image_paths.len()).with_series(series);
+                create_cbz_from_images(&first.image_paths, &partial_output_path, &info)
+            }
+            OutputFormat::Epub => {
+                // Authors often draw their series too
+                let mut creators = series.authors.clone();
+                creators.extend(series.artists.iter().filter(|artist| !series.authors.contains(artist)).cloned());
+                let options = EpubOptions {
+                    title: document_title,
+                    right_to_left: !export.left_to_right,
+                    spread: export.epub_spread,
+                    source_url: first.url.clone(),
+                    cover: cover.clone(),
+                    creators,
+                    description: series.description.clone(),
+                    subjects: series.genres.iter().chain(&series.tags).cloned().collect(),
+                    ..EpubOptions::default()
+                };
+                let epub_chapters = chapters.iter()
+                    .map(|chapter| EpubChapter { title: chapter.title.clone(), image_paths: chapter.image_paths.clone() })
+                    .collect::<Vec<_>>();
+                create_epub(&epub_chapters, &partial_output_path, &options)
+            }
+        };
+
+        let result = result.and_then(|_| std::fs::rename(&partial_output_path, &output_path).map_err(DownloadError::IoError));
+        match result {
+            Ok(_) => {
+                info!("✓ {} created successfully", target.format.extension().to_uppercase());
+                let chapter_urls = chapters.iter().map(|chapter| chapter.url.as_str()).collect::<Vec<_>>();
+                journal.record_export(&output_path, &fingerprint, &chapter_urls);
+                done.extend(chapter_urls.iter().map(|url| url.to_string()));
+                if let Err(e) = journal.save() {
+                    warn!("Failed to save download journal: {}", e);
+                }
+
+                let manifest_chapters = chapters.iter()
+                    .map(|chapter| ManifestChapter { url: chapter.url.clone(), title: chapter.title.clone(), page_count: chapter.image_paths.len() })
+                    .collect();
+                if let Err(e) = manifest.record(&output_path, target.format.extension(), manifest_chapters) {
+                    warn!("Failed to record {} in the manifest: {}", output_path.display(), e);
+                }
+                if let Err(e) = manifest.save() {
+                    warn!("Failed to save manifest: {}", e);
+                }
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial_output_path);
+                error!("✗ Failed to create {}: {}", target.format.extension().to_uppercase(), e);
+            }
+        }
+    }
+
+    done
+}
+
+// Output file name of a single chapter, from its number when the title has one
+fn chapter_file_stem(chapter: &ChapterInfo) -> String {
+    chapter.number.file_stem().unwrap_or_else(|| sanitize_filename(&chapter.title))
+}
+
 // Function to let user select which chapters to download
 fn select_chapters(chapters: &[ChapterInfo]) -> Result<Vec<usize>, DownloadError> {
     info!("Displaying available chapters");
@@ -257,19 +1263,19 @@ fn select_chapters(chapters: &[ChapterInfo]) -> Result<Vec<usize>, DownloadError
         // If not the last chunk, wait for the user to continue
         if chunk.len() == chunk_size && chapter_index_of_last(chunk) + 1 < chapters.len() {
             print!("Press Enter to see more chapters...");
-            io::stdout().flush().map_err(|e| DownloadError::IoError(e))?;
+            io::stdout().flush().map_err(DownloadError::IoError)?;
             let mut input = String::new();
-            io::stdin().read_line(&mut input).map_err(|e| DownloadError::IoError(e))?;
+            io::stdin().read_line(&mut input).map_err(DownloadError::IoError)?;
             debug!("User pressed Enter to continue viewing chapters");
         }
     }
 
     println!("\nEnter chapter numbers to download (comma-separated, ranges allowed e.g. '1,3-5,7'):");
     print!("> ");
-    io::stdout().flush().map_err(|e| DownloadError::IoError(e))?;
+    io::stdout().flush().map_err(DownloadError::IoError)?;
 
     let mut input = String::new();
-    io::stdin().read_line(&mut input).map_err(|e| DownloadError::IoError(e))?;
+    io::stdin().read_line(&mut input).map_err(DownloadError::IoError)?;
     debug!("User input for chapter selection: '{}'", input.trim());
 
     // Parse the comma-separated selection including ranges
@@ -346,3 +1352,81 @@ fn parse_chapter_selection(input: &str, max_chapters: usize) -> Result<Vec<usize
 
     Ok(selected)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_is_consistent() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from(["download-manga", "cache", "stats", "--cache-dir", "/tmp/cache"]).unwrap();
+        assert!(matches!(cli.command, Command::Cache { ref dir, action: CacheAction::Stats } if dir.cache_dir.as_deref() == Some("/tmp/cache")));
+        // Downloading still needs a link and an output directory
+        assert!(Cli::try_parse_from(["download-manga", "download", "--link", "https://example.com/manga/x/"]).is_err());
+    }
+
+    #[test]
+    fn test_network_options() {
+        let cli = Cli::try_parse_from(["download-manga", "list", "--link", "https://example.com/manga/x/", "--retries", "1", "--retry-status", "429,503"]).unwrap();
+        let Command::List { network, .. } = cli.command else { panic!("expected the list command") };
+        assert_eq!(network.retries, 1);
+        assert_eq!(network.retry_statuses, vec![429, 503]);
+
+        assert!(Cli::try_parse_from(["download-manga", "list", "--link", "https://example.com/manga/x/", "--retry-jitter", "1.5"]).is_err());
+        for rate in ["NaN", "-1", "inf"] {
+            assert!(Cli::try_parse_from(["download-manga", "list", "--link", "https://example.com/manga/x/", "--rate-limit", rate]).is_err(), "{}", rate);
+        }
+    }
+
+    #[test]
+    fn test_processing_options() {
+        let cli = Cli::try_parse_from(["download-manga", "export", "--output-dir", "out", "--max-dimension", "1600", "--image-format", "webp"]).unwrap();
+        let Command::Export { processing, .. } = cli.command else { panic!("expected the export command") };
+        assert_eq!(processing.max_dimension, Some(1600));
+        assert_eq!(processing.image_format, OutputImageFormat::Webp);
+
+        assert!(Cli::try_parse_from(["download-manga", "export", "--output-dir", "out", "--max-dimension", "0"]).is_err());
+    }
+
+    fn followed(url: String, title: &str) -> FollowedSeries {
+        FollowedSeries {
+            url,
+            title: title.to_string(),
+            output_dir: PathBuf::from("/tmp/example"),
+            format: String::from("pdf"),
+            seen_chapters: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preview_updates_skips_series_that_fail() {
+        let mut server = mockito::Server::new_async().await;
+        // No chapter list, not even through the AJAX endpoints
+        let _broken = server.mock("GET", "/manga/broken/")
+            .with_body(r#"<div class="post-title"><h1>Broken</h1></div>"#)
+            .create_async().await;
+        let _good = server.mock("GET", "/manga/good/")
+            .with_body(r#"<div class="post-title"><h1>Good</h1></div>
+                <li class="wp-manga-chapter"><a href="https://example.com/manga/good/chapter-2/">Chapter 2</a></li>
+                <li class="wp-manga-chapter"><a href="https://example.com/manga/good/chapter-1/">Chapter 1</a></li>"#)
+            .create_async().await;
+
+        let session = Session {
+            registry: SourceRegistry::default(),
+            client: HttpClient::new(&HttpConfig::default()).unwrap(),
+            cookies: Vec::new(),
+            cache_manager: None,
+        };
+        let mut library = Library::default();
+        library.follow(followed(format!("{}/manga/broken/", server.url()), "Broken"));
+        let mut good = followed(format!("{}/manga/good/", server.url()), "Good");
+        good.seen_chapters.insert(String::from("https://example.com/manga/good/chapter-1/"));
+        library.follow(good);
+
+        let previews = preview_updates(&session, &library, 1).await;
+        assert_eq!(previews, vec![(String::from("Good"), vec![String::from("Chapter 2")])]);
+    }
+}
@@ -1,8 +1,14 @@
+use crate::chapter_number::{sort_by_number, ChapterNumber};
 use crate::chapter_to_download::ChapterToDownload;
 use crate::error::DownloadError;
+use crate::http::{HttpClient, HttpConfig};
+use crate::metadata::SeriesMetadata;
+use crate::source::{Source, SourceRegistry};
 use futures::{stream, StreamExt};
 use indicatif::{ProgressBar, ProgressStyle, MultiProgress, ProgressState};
+use log::{debug, info, warn};
 use std::fmt::Write;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Debug)]
@@ -10,18 +16,40 @@ pub struct ChapterInfo {
     pub index: usize,
     pub title: String,
     pub url: String,
+    /// Volume, chapter number and subtitle parsed from the title and URL
+    pub number: ChapterNumber,
+}
+
+impl ChapterInfo {
+  pub fn new(index: usize, title: String, url: String) -> Self {
+    let number = ChapterNumber::parse(&title, &url);
+    ChapterInfo { index, title, url, number }
+  }
 }
 
 pub struct MangaToDownload {
   pub link: String,
   pub title: String,
+  /// Everything else the series page says about the series
+  pub metadata: SeriesMetadata,
   pub chapters: Vec<ChapterToDownload>,
   pub document: scraper::Html,
+  // Chapter list loaded separately when the series page doesn't embed it
+  pub chapter_list_document: Option<scraper::Html>,
   pub concurrency: usize,
+  pub source: Arc<dyn Source>,
+  pub client: HttpClient,
 }
 
 impl MangaToDownload {
   pub async fn new(link: String, concurrency: usize) -> Result<Self, DownloadError> {
+      let source = SourceRegistry::default().source_for(&link)?;
+      let client = HttpClient::new(&HttpConfig::default())?;
+      Self::with_source(link, concurrency, source, client).await
+  }
+
+  // Load a manga using an explicitly chosen source and a shared HTTP client
+  pub async fn with_source(link: String, concurrency: usize, source: Arc<dyn Source>, client: HttpClient) -> Result<Self, DownloadError> {
       // Create a spinner for initialization
       let spinner = ProgressBar::new_spinner();
       spinner.set_style(
@@ -34,33 +62,71 @@ impl MangaToDownload {
       spinner.set_message("Fetching manga information...");
       spinner.enable_steady_tick(Duration::from_millis(100));
 
-      let response = reqwest::get(&link).await?;
-      let body = response.text().await?;
-      let document = scraper::Html::parse_document(&body.trim());
+      let body = client.get_text(&link).await?;
+      let document = scraper::Html::parse_document(body.trim());
       let mut manga = Self {
           link,
           title: String::new(),
+          metadata: SeriesMetadata::default(),
           chapters: Vec::new(),
           document,
+          chapter_list_document: None,
           concurrency,
+          source,
+          client,
       };
 
       spinner.set_message("Processing manga title...");
       manga.process_title()?;
+      manga.process_metadata();
+
+      if manga.source.chapters(&manga.document)?.is_empty() {
+          spinner.set_message("Loading chapter list...");
+          manga.chapter_list_document = manga.fetch_chapter_list().await;
+      }
 
       spinner.finish_with_message(format!("✓ Found manga: {}", manga.title));
       Ok(manga)
   }
 
   fn process_title(&mut self) -> Result<(), DownloadError> {
-      let title_selector = scraper::Selector::parse(".post-title h1")
-          .map_err(|_| DownloadError::SelectorError(String::from("Failed to parse .post-title h1 selector")))?;
+      self.title = self.source.series_title(&self.document)?;
+      Ok(())
+  }
 
-      let title = self.document.select(&title_selector).next()
-          .ok_or_else(|| DownloadError::ElementNotFound(String::from("Manga title element not found")))?;
+  fn process_metadata(&mut self) {
+      let mut metadata = self.source.series_metadata(&self.document);
+      metadata.title = self.title.clone();
+      metadata.url = self.link.clone();
+      metadata.authors = self.source.series_authors(&self.document);
+      metadata.genres = self.source.series_genres(&self.document);
+      // Covers are often linked relative to the series page
+      metadata.cover_url = metadata.cover_url
+          .map(|cover| reqwest::Url::parse(&self.link).and_then(|link| link.join(&cover)).map(String::from).unwrap_or(cover));
+      self.metadata = metadata;
+  }
 
-      self.title = title.text().collect::<Vec<_>>().join(" ");
-      Ok(())
+  // Fetch the chapter list through the source's fallback requests (e.g. Madara AJAX endpoints)
+  async fn fetch_chapter_list(&self) -> Option<scraper::Html> {
+      for request in self.source.chapter_list_requests(&self.link, &self.document) {
+          info!("Chapter list not embedded in series page, requesting {}", request.url);
+
+          match self.client.post_form(&request.url, &request.form).await {
+              Ok(body) => {
+                  let fragment = scraper::Html::parse_fragment(body.trim());
+                  match self.source.chapters(&fragment) {
+                      Ok(chapters) if !chapters.is_empty() => {
+                          debug!("Found {} chapters via {}", chapters.len(), request.url);
+                          return Some(fragment);
+                      }
+                      _ => debug!("No chapters in response from {}", request.url),
+                  }
+              }
+              Err(e) => warn!("Chapter list request to {} failed: {}", request.url, e),
+          }
+      }
+
+      None
   }
 
   // New method to list available chapters without downloading them
@@ -76,39 +142,22 @@ impl MangaToDownload {
       spinner.set_message("Scanning for available chapters...");
       spinner.enable_steady_tick(Duration::from_millis(100));
 
-      let list_of_chapters_selector = scraper::Selector::parse(".wp-manga-chapter a")
-          .map_err(|_| DownloadError::SelectorError(String::from("Failed to parse .wp-manga-chapter a selector")))?;
-
-      let chapters = self.document.select(&list_of_chapters_selector)
-          .filter_map(|e| {
-              let url = e.attr("href")?.to_string();
-              let title = e.text().collect::<Vec<_>>().join(" ").trim().to_string();
-              Some(ChapterInfo {
-                  index: 0, // Will be updated after collection
-                  title,
-                  url,
-              })
-          })
-          .collect::<Vec<_>>();
+      let document = self.chapter_list_document.as_ref().unwrap_or(&self.document);
+      let chapters = self.source.chapters(document)?;
 
       if chapters.is_empty() {
           spinner.finish_with_message("✗ No chapters found for this manga");
           return Err(DownloadError::ElementNotFound(String::from("No chapters found for this manga")));
       }
 
-      // Number the chapters in reverse order (newest first) and return them
-      let mut numbered_chapters = chapters
-          .into_iter()
-          .rev() // Reverse to get newest first
-          .enumerate()
-          .map(|(i, mut chapter)| {
-              chapter.index = i;
-              chapter
-          })
-          .collect::<Vec<_>>();
+      // Sites list the newest chapter first; reverse to get reading order (oldest first)
+      let mut numbered_chapters = chapters.into_iter().rev().collect::<Vec<_>>();
 
-      // Sort by index so they're in a logical order (usually newest first)
-      numbered_chapters.sort_by_key(|c| c.index);
+      // Fix chapters posted out of order, then number them by position
+      sort_by_number(&mut numbered_chapters, |chapter| &chapter.number);
+      for (i, chapter) in numbered_chapters.iter_mut().enumerate() {
+          chapter.index = i;
+      }
 
       spinner.finish_with_message(format!("✓ Found {} chapters", numbered_chapters.len()));
       Ok(numbered_chapters)
@@ -170,6 +219,8 @@ impl MangaToDownload {
       let mut successful_chapters = Vec::new();
       let mut failed_chapters = 0;
 
+      let source = Arc::clone(&self.source);
+      let client = &self.client;
       let mut chapter_stream = stream::iter(chapters_to_download.into_iter().enumerate())
           .map(|(idx, chapter)| {
               let chapter_pb = multi_progress.add(ProgressBar::new_spinner());
@@ -177,9 +228,10 @@ impl MangaToDownload {
               chapter_pb.set_prefix(format!("[Chapter {}/{}]", idx + 1, chapters_count));
               chapter_pb.set_message(format!("Downloading: {}", chapter.title));
               chapter_pb.enable_steady_tick(Duration::from_millis(100));
+              let source = Arc::clone(&source);
 
               async move {
-                  let result = ChapterToDownload::new(chapter.url.clone()).await;
+                  let result = ChapterToDownload::new(chapter.url.clone(), source, client).await;
                   (chapter, result, chapter_pb)
               }
           })
@@ -189,7 +241,7 @@ impl MangaToDownload {
           match result {
               Ok(chapter) => {
                   chapter_pb.finish_with_message(format!("✓ Downloaded: {}", chapter.title));
-                  successful_chapters.push(chapter);
+                  successful_chapters.push((chapter_info.index, chapter));
                   main_pb.inc(1);
               }
               Err(err) => {
@@ -201,7 +253,9 @@ impl MangaToDownload {
           }
       }
 
-      self.chapters = successful_chapters;
+      // Chapters finish in any order; keep them in reading order
+      successful_chapters.sort_by_key(|(index, _)| *index);
+      self.chapters = successful_chapters.into_iter().map(|(_, chapter)| chapter).collect();
       main_pb.finish_with_message(format!("Downloaded {} chapters", self.chapters.len()));
 
       if self.chapters.is_empty() {
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::calculate_file_checksum;
+use crate::error::DownloadError;
+use crate::journal::{load_state_file, save_state_file};
+
+/// Name of the manifest file in the output directory
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// A chapter in an exported file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestChapter {
+    /// URL of the chapter
+    pub url: String,
+    /// Title of the chapter
+    pub title: String,
+    /// Number of pages of the chapter in the file
+    pub page_count: usize,
+}
+
+/// An exported file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Format of the file, as its extension
+    pub format: String,
+    /// Chapters in the file, in reading order
+    pub chapters: Vec<ManifestChapter>,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// SHA-256 checksum of the file
+    pub checksum: String,
+}
+
+/// Files exported to an output directory, by path relative to the directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Directory the manifest belongs to
+    #[serde(skip)]
+    output_dir: PathBuf,
+    #[serde(default)]
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Load the manifest of `output_dir`, starting an empty one when there is none yet
+    pub fn load(output_dir: &Path) -> Result<Self, DownloadError> {
+        let mut manifest: Manifest = load_state_file(&output_dir.join(MANIFEST_FILE_NAME), "manifest")?;
+        manifest.output_dir = output_dir.to_path_buf();
+        Ok(manifest)
+    }
+
+    /// Save the manifest, replacing the previous version atomically
+    pub fn save(&self) -> Result<(), DownloadError> {
+        save_state_file(&self.output_dir.join(MANIFEST_FILE_NAME), self, "manifest")
+    }
+
+    /// Whether the chapter at `url` was exported to a file in `format` that is still there
+    /// with the recorded checksum
+    pub fn is_exported(&self, url: &str, format: &str) -> bool {
+        self.files.iter().any(|(output, entry)| {
+            entry.format == format
+                && entry.chapters.iter().any(|chapter| chapter.url == url)
+                && self.is_intact(output, entry)
+        })
+    }
+
+    /// Whether the file of `entry` is unchanged, comparing the size before hashing the content
+    fn is_intact(&self, output: &str, entry: &ManifestEntry) -> bool {
+        let path = self.output_dir.join(output);
+        fs::metadata(&path).is_ok_and(|metadata| metadata.len() == entry.size)
+            && calculate_file_checksum(&path).is_ok_and(|checksum| checksum == entry.checksum)
+    }
+
+    /// Record that `chapters` were exported to `output_path` in `format`
+    pub fn record(&mut self, output_path: &Path, format: &str, chapters: Vec<ManifestChapter>) -> Result<(), DownloadError> {
+        let output = output_path.strip_prefix(&self.output_dir)
+            .unwrap_or(output_path)
+            .to_string_lossy()
+            .into_owned();
+
+        debug!("Recording {} chapters in {} in the manifest", chapters.len(), output);
+        self.files.insert(output, ManifestEntry {
+            format: format.to_string(),
+            chapters,
+            size: fs::metadata(output_path)?.len(),
+            checksum: calculate_file_checksum(output_path)?,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(url: &str, title: &str, page_count: usize) -> ManifestChapter {
+        ManifestChapter { url: url.to_string(), title: title.to_string(), page_count }
+    }
+
+    #[test]
+    fn test_manifest_tracks_exported_chapters() {
+        let dir = std::env::temp_dir().join("manga_downloader_manifest_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("chapter-1.pdf");
+        fs::write(&output, b"%PDF-1.7 a").unwrap();
+
+        let mut manifest = Manifest::load(&dir).unwrap();
+        assert!(!manifest.is_exported("https://example.com/c/1", "pdf"));
+        manifest.record(&output, "pdf", vec![chapter("https://example.com/c/1", "Chapter 1", 12)]).unwrap();
+        manifest.save().unwrap();
+
+        let manifest = Manifest::load(&dir).unwrap();
+        let entry = &manifest.files["chapter-1.pdf"];
+        assert_eq!(entry.format, "pdf");
+        assert_eq!(entry.chapters[0].page_count, 12);
+        assert_eq!(entry.checksum.len(), 64);
+        assert!(manifest.is_exported("https://example.com/c/1", "pdf"));
+
+        // A file with the same size but different content no longer counts
+        fs::write(&output, b"%PDF-1.7 b").unwrap();
+        assert!(!manifest.is_exported("https://example.com/c/1", "pdf"));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_manifest_export_is_per_format() {
+        let dir = std::env::temp_dir().join("manga_downloader_manifest_format_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let pdf = dir.join("chapter-1.pdf");
+        fs::write(&pdf, b"%PDF-1.7").unwrap();
+
+        let mut manifest = Manifest::load(&dir).unwrap();
+        manifest.record(&pdf, "pdf", vec![chapter("https://example.com/c/1", "Chapter 1", 12)]).unwrap();
+        assert!(manifest.is_exported("https://example.com/c/1", "pdf"));
+        // A PDF run doesn't count as a CBZ export
+        assert!(!manifest.is_exported("https://example.com/c/1", "cbz"));
+
+        let cbz = dir.join("chapter-1.cbz");
+        fs::write(&cbz, b"PK").unwrap();
+        manifest.record(&cbz, "cbz", vec![chapter("https://example.com/c/1", "Chapter 1", 12)]).unwrap();
+        assert!(manifest.is_exported("https://example.com/c/1", "cbz"));
+        assert!(manifest.is_exported("https://example.com/c/1", "pdf"));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::Path;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::downloader::write_file_atomic;
+use crate::error::DownloadError;
+
+/// Name of the series metadata file in the output directory
+pub const SERIES_FILE_NAME: &str = "series.json";
+
+/// Publication status of a series
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeriesStatus {
+    Ongoing,
+    Completed,
+    Hiatus,
+    Cancelled,
+}
+
+impl SeriesStatus {
+    /// Parse a status as sites write it, e.g. "OnGoing" or "Completed"
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_lowercase();
+        if text.contains("ongoing") || text.contains("on going") || text.contains("publishing") {
+            Some(SeriesStatus::Ongoing)
+        } else if text.contains("complete") || text.contains("finished") || text.contains("ended") {
+            Some(SeriesStatus::Completed)
+        } else if text.contains("hiatus") || text.contains("on hold") {
+            Some(SeriesStatus::Hiatus)
+        } else if text.contains("cancel") || text.contains("dropped") {
+            Some(SeriesStatus::Cancelled)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SeriesStatus::Ongoing => "Ongoing",
+            SeriesStatus::Completed => "Completed",
+            SeriesStatus::Hiatus => "Hiatus",
+            SeriesStatus::Cancelled => "Cancelled",
+        }
+    }
+}
+
+/// What a series page says about the series, saved as `series.json` next to the downloads
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeriesMetadata {
+    pub title: String,
+    /// Link to the series page
+    pub url: String,
+    /// Other titles of the series, e.g. in the original language
+    pub alternative_titles: Vec<String>,
+    pub authors: Vec<String>,
+    pub artists: Vec<String>,
+    pub genres: Vec<String>,
+    pub tags: Vec<String>,
+    pub status: Option<SeriesStatus>,
+    pub release_year: Option<u32>,
+    /// Average rating as shown by the site, usually out of 5
+    pub rating: Option<f32>,
+    pub description: Option<String>,
+    pub cover_url: Option<String>,
+}
+
+impl SeriesMetadata {
+    /// Load the metadata saved in `output_dir`, if there is any
+    pub fn load(output_dir: &Path) -> Option<Self> {
+        let path = output_dir.join(SERIES_FILE_NAME);
+        let data = fs::read(&path).ok()?;
+        match serde_json::from_slice::<SeriesMetadata>(&data) {
+            Ok(metadata) => {
+                debug!("Loaded series metadata from {:?}", path);
+                Some(metadata)
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable series metadata {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Save the metadata as `series.json` in `output_dir`, replacing the previous version atomically
+    pub fn save(&self, output_dir: &Path) -> Result<(), DownloadError> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| DownloadError::ParsingError(format!("Failed to write series metadata: {}", e)))?;
+        write_file_atomic(&output_dir.join(SERIES_FILE_NAME), &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_series_metadata_round_trip() {
+        let dir = std::env::temp_dir().join("manga_downloader_series_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(SeriesMetadata::load(&dir), None);
+
+        let metadata = SeriesMetadata {
+            title: String::from("Example"),
+            authors: vec![String::from("Someone")],
+            status: SeriesStatus::parse("OnGoing"),
+            release_year: Some(2019),
+            rating: Some(4.5),
+            ..SeriesMetadata::default()
+        };
+        metadata.save(&dir).unwrap();
+
+        let loaded = SeriesMetadata::load(&dir).unwrap();
+        assert_eq!(loaded, metadata);
+        assert_eq!(loaded.status, Some(SeriesStatus::Ongoing));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,262 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::downloader::sanitize_filename;
+use crate::error::DownloadError;
+
+/// Values the placeholders of a [`NameTemplate`] are replaced with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NameValues {
+    /// `{series}`: title of the series
+    pub series: String,
+    /// `{title}`: title of the chapter, or of the volume for merged exports
+    pub title: String,
+    /// `{chapter}`: chapter number
+    pub chapter: Option<f64>,
+    /// `{volume}`: volume number
+    pub volume: Option<u32>,
+    /// `{host}`: host name of the site, e.g. www.mangaread.org
+    pub host: String,
+    /// `{date}`: day of the export as YYYY-MM-DD
+    pub date: String,
+    /// `{pages}`: number of pages in the file
+    pub pages: usize,
+    /// `{ext}`: file extension of the export format, without the dot
+    pub ext: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Series,
+    Title,
+    Chapter,
+    Volume,
+    Host,
+    Date,
+    Pages,
+    Ext,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    /// A placeholder, zero-padded to this width
+    Field(Field, usize),
+}
+
+/// Output path template such as `{series}/Vol.{volume:02}/{series} - Ch.{chapter:04} - {title}.{ext}`
+///
+/// `/` separates directories. Placeholder values go through [`sanitize_filename`], so they
+/// can't add directories or characters the file system rejects; the text around them is
+/// kept as written. Numbers are zero-padded with `{name:0N}`. A directory whose placeholder
+/// has no value, such as `Vol.{volume}` for a chapter without a volume, is left out, and
+/// in the file name a missing value is left empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    components: Vec<Vec<Segment>>,
+}
+
+impl FromStr for NameTemplate {
+    type Err = DownloadError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| DownloadError::ParsingError(format!("Invalid name template '{}': {}", value, reason));
+
+        let mut components = Vec::new();
+        for component in value.split('/') {
+            if component.is_empty() {
+                return Err(invalid("empty directory or file name"));
+            }
+
+            let mut segments = Vec::new();
+            let mut rest = component;
+            while let Some(start) = rest.find('{') {
+                if start > 0 {
+                    segments.push(Segment::Text(rest[..start].to_string()));
+                }
+                let end = rest[start..].find('}').ok_or_else(|| invalid("unclosed '{'"))? + start;
+                let (name, width) = match rest[start + 1..end].split_once(':') {
+                    Some((name, spec)) => {
+                        let width = spec.strip_prefix('0')
+                            .and_then(|width| width.parse::<usize>().ok())
+                            .ok_or_else(|| invalid("padding must look like ':04'"))?;
+                        (name, width)
+                    }
+                    None => (&rest[start + 1..end], 0),
+                };
+
+                let field = match name {
+                    "series" => Field::Series,
+                    "title" => Field::Title,
+                    "chapter" => Field::Chapter,
+                    "volume" => Field::Volume,
+                    "host" => Field::Host,
+                    "date" => Field::Date,
+                    "pages" => Field::Pages,
+                    "ext" => Field::Ext,
+                    other => return Err(invalid(&format!("unknown placeholder '{{{}}}'", other))),
+                };
+                if width > 0 && !matches!(field, Field::Chapter | Field::Volume | Field::Pages) {
+                    return Err(invalid("only chapter, volume and pages can be padded"));
+                }
+
+                segments.push(Segment::Field(field, width));
+                rest = &rest[end + 1..];
+            }
+            if rest.contains('}') {
+                return Err(invalid("unmatched '}'"));
+            }
+            if !rest.is_empty() {
+                segments.push(Segment::Text(rest.to_string()));
+            }
+            components.push(segments);
+        }
+
+        Ok(NameTemplate { components })
+    }
+}
+
+impl NameTemplate {
+    /// Path of an exported file relative to the output directory
+    pub fn render(&self, values: &NameValues) -> PathBuf {
+        let last = self.components.len() - 1;
+        let mut path = PathBuf::new();
+
+        for (i, segments) in self.components.iter().enumerate() {
+            let mut name = String::new();
+            let mut complete = true;
+            for segment in segments {
+                match segment {
+                    Segment::Text(text) => name.push_str(text),
+                    Segment::Field(field, width) => match field_value(*field, *width, values) {
+                        Some(value) => name.push_str(&sanitize_filename(&value)),
+                        None => complete = false,
+                    },
+                }
+            }
+
+            if complete || i == last {
+                path.push(name);
+            }
+        }
+
+        path
+    }
+}
+
+fn field_value(field: Field, width: usize, values: &NameValues) -> Option<String> {
+    let text = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    match field {
+        Field::Series => text(&values.series),
+        Field::Title => text(&values.title),
+        Field::Host => text(&values.host),
+        Field::Date => text(&values.date),
+        Field::Ext => text(&values.ext),
+        Field::Volume => values.volume.map(|volume| format!("{:0width$}", volume, width = width)),
+        Field::Pages => Some(format!("{:0width$}", values.pages, width = width)),
+        Field::Chapter => values.chapter.map(|chapter| {
+            let number = chapter.to_string();
+            match number.split_once('.') {
+                Some((whole, fraction)) => format!("{:0>width$}.{}", whole, fraction, width = width),
+                None => format!("{:0>width$}", number, width = width),
+            }
+        }),
+    }
+}
+
+/// Today's date in UTC as YYYY-MM-DD
+pub fn today() -> String {
+    civil_date(seconds_since_epoch() / 86_400)
+}
+
+/// Current UTC time as YYYY-MM-DDThh:mm:ssZ, as EPUB's `dcterms:modified` requires
+pub(crate) fn utc_timestamp() -> String {
+    let seconds = seconds_since_epoch();
+    let time = seconds % 86_400;
+    format!("{}T{:02}:{:02}:{:02}Z", civil_date(seconds / 86_400), time / 3_600, time % 3_600 / 60, time % 60)
+}
+
+fn seconds_since_epoch() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Date `days` days after 1970-01-01 as YYYY-MM-DD
+fn civil_date(days: u64) -> String {
+    // Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn values() -> NameValues {
+        NameValues {
+            series: String::from("Example Manga"),
+            title: String::from("The Return"),
+            chapter: Some(10.5),
+            volume: Some(2),
+            host: String::from("www.mangaread.org"),
+            date: String::from("2024-05-01"),
+            pages: 18,
+            ext: String::from("pdf"),
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let template = "{series}/Vol.{volume:02}/{series} - Ch.{chapter:04} - {title}.{ext}".parse::<NameTemplate>().unwrap();
+        assert_eq!(
+            template.render(&values()),
+            Path::new("example-manga/Vol.02/example-manga - Ch.0010.5 - the-return.pdf")
+        );
+
+        // Without a volume the volume directory is left out
+        let without_volume = NameValues { volume: None, ..values() };
+        assert_eq!(
+            template.render(&without_volume),
+            Path::new("example-manga/example-manga - Ch.0010.5 - the-return.pdf")
+        );
+
+        let template = "{host}_{date}_{pages:03}.{ext}".parse::<NameTemplate>().unwrap();
+        assert_eq!(template.render(&values()), Path::new("www.mangaread.org_2024-05-01_018.pdf"));
+    }
+
+    #[test]
+    fn test_values_cannot_add_directories() {
+        let template = "{title}.{ext}".parse::<NameTemplate>().unwrap();
+        let values = NameValues { title: String::from("../1/2"), ..values() };
+        assert_eq!(template.render(&values).components().count(), 1);
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for template in ["", "{series", "{unknown}.pdf", "a//b", "/{series}", "{title:04}", "{chapter:4}", "x}"] {
+            assert!(template.parse::<NameTemplate>().is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(19_782), "2024-02-29");
+        assert_eq!(today().len(), 10);
+
+        let timestamp = utc_timestamp();
+        assert_eq!(timestamp.len(), 20);
+        assert!(timestamp.ends_with('Z'));
+        assert_eq!(&timestamp[10..11], "T");
+    }
+}
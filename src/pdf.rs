@@ -1,418 +1,522 @@
-use std::path::Path;
-use std::env;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use log::{debug, info, trace};
+use pdf_writer::types::PageMode;
+use pdf_writer::writers::{Catalog, DocumentInfo};
+use pdf_writer::{Chunk, Content, Filter, Name, Rect, Ref, TextStr};
+
 use crate::error::DownloadError;
-use image;
-use log::{debug, info, warn, trace};
 
-/// Generates a PDF from a collection of image paths
-pub fn create_pdf_from_images(image_paths: &[impl AsRef<Path>], output_path: &Path) -> Result<(), DownloadError> {
-    if image_paths.is_empty() {
-        return Err(DownloadError::PdfGenerationError(String::from("Cannot create PDF: no images provided")));
+/// Points per millimetre (PDF units are 1/72 inch)
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+/// Largest page dimension allowed by the PDF specification (200 inches)
+const MAX_PAGE_SIZE: f32 = 14_400.0;
+
+/// In `fit-page` mode, images more than this many pages tall are split instead of shrunk
+const TALL_IMAGE_PAGES: f32 = 2.0;
+
+/// How images are placed on PDF pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PdfLayout {
+    /// Fit each image on a page of the chosen paper size, with margins
+    #[default]
+    FitPage,
+    /// Size each page exactly to its image, without margins
+    ImageSize,
+    /// Scale each image to the paper width; the page height follows the image
+    FitWidth,
+}
+
+/// Paper sizes available for the `fit-page` and `fit-width` layouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PaperSize {
+    /// 210 x 297 mm
+    #[default]
+    A4,
+    /// 8.5 x 11 in
+    Letter,
+    /// 176 x 250 mm, the usual size of printed manga volumes
+    B5,
+}
+
+impl PaperSize {
+    /// Width and height of the paper in points, in portrait orientation
+    pub fn dimensions(self) -> (f32, f32) {
+        match self {
+            PaperSize::A4 => (210.0 * POINTS_PER_MM, 297.0 * POINTS_PER_MM),
+            PaperSize::Letter => (612.0, 792.0),
+            PaperSize::B5 => (176.0 * POINTS_PER_MM, 250.0 * POINTS_PER_MM),
+        }
     }
+}
 
-    debug!("Creating PDF from {} images", image_paths.len());
-    trace!("Output path: {:?}", output_path);
+/// Settings for PDF generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfOptions {
+    /// How images are placed on pages
+    pub layout: PdfLayout,
+    /// Paper size for the `fit-page` and `fit-width` layouts
+    pub paper_size: PaperSize,
+    /// Margin around the image in millimetres, used by the `fit-page` layout
+    pub margin_mm: f32,
+    /// Image added as the first page, without a bookmark
+    pub cover: Option<PathBuf>,
+}
 
-    // Try to find a suitable font
-    let font_family = find_system_font()
-        .map_err(|e| DownloadError::PdfGenerationError(format!("Failed to load font: {}", e)))?;
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            layout: PdfLayout::default(),
+            paper_size: PaperSize::default(),
+            margin_mm: 6.0,
+            cover: None,
+        }
+    }
+}
 
-    let mut doc = genpdf::Document::new(font_family);
+/// Document information written to the PDF, shown by readers in the document properties
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfMetadata {
+    /// Document title, usually the series title and chapter or volume
+    pub title: Option<String>,
+    /// Authors of the series
+    pub author: Option<String>,
+    /// What the document contains, e.g. the chapter range
+    pub subject: Option<String>,
+    /// Keywords such as the genres of the series
+    pub keywords: Vec<String>,
+}
 
-    // Configure document properties
-    doc.set_title("Manga Chapter");
-    doc.set_paper_size(genpdf::PaperSize::A4);
+/// A chapter of a PDF: a title for the bookmark outline and its page images in order
+#[derive(Debug, Clone)]
+pub struct PdfChapter {
+    /// Bookmark title; chapters with an empty title get no bookmark
+    pub title: String,
+    /// Page images in reading order
+    pub image_paths: Vec<PathBuf>,
+}
 
-    // Get page width (in mm)
-    // A4 paper size is 210mm x 297mm
-    let page_width = 210.0;
+/// Where an image (or a slice of a tall image) goes on one PDF page, in points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PagePlacement {
+    /// Width of the page
+    pub page_width: f32,
+    /// Height of the page
+    pub page_height: f32,
+    /// Rectangle the whole image is drawn into; it may extend past the page for slices
+    pub image: [f32; 4],
+    /// Area outside of which nothing is drawn, set for slices of a split image
+    pub clip: Option<[f32; 4]>,
+}
 
-    // Add each image to the document
-    for (i, path) in image_paths.iter().enumerate() {
-        trace!("Processing image {}/{}", i+1, image_paths.len());
+/// Generates a PDF from a collection of image paths with the default options
+pub fn create_pdf_from_images(image_paths: &[impl AsRef<Path>], output_path: &Path) -> Result<(), DownloadError> {
+    create_pdf(image_paths, output_path, &PdfOptions::default())
+}
 
-        // Load the image to get its dimensions
-        let img_data = load_image_from_path(path)?;
-        let img_width = img_data.width() as f64;
+/// Generates a PDF from a collection of image paths, laying out pages according to `options`
+pub fn create_pdf(image_paths: &[impl AsRef<Path>], output_path: &Path, options: &PdfOptions) -> Result<(), DownloadError> {
+    let chapter = PdfChapter {
+        title: String::new(),
+        image_paths: image_paths.iter().map(|path| path.as_ref().to_path_buf()).collect(),
+    };
+    create_volume_pdf(&[chapter], output_path, options, &PdfMetadata::default())
+}
 
-        // Calculate scale to fit image to page width (considering margins)
-        // Assuming 10mm margins on each side (20mm total horizontal margins)
-        let available_width = page_width - 12.0; // Available width in mm
+/// Generates one PDF from several chapters, with a bookmark pointing to the first page of each
+///
+/// Pages are streamed to the file as they are laid out, so only one image is held in
+/// memory at a time. JPEG pages are embedded as they are, without being decoded.
+pub fn create_volume_pdf(chapters: &[PdfChapter], output_path: &Path, options: &PdfOptions, metadata: &PdfMetadata) -> Result<(), DownloadError> {
+    let image_count = chapters.iter().map(|chapter| chapter.image_paths.len()).sum::<usize>();
+    if image_count == 0 {
+        return Err(DownloadError::PdfGenerationError(String::from("Cannot create PDF: no images provided")));
+    }
 
-        // Convert image width to mm (assuming 300 DPI)
-        let img_width_mm = img_width * 25.4 / 300.0;
+    // The cover is an untitled chapter of its own, so it gets no bookmark
+    let cover = options.cover.as_ref().map(|cover| PdfChapter { title: String::new(), image_paths: vec![cover.clone()] });
+    let image_count = image_count + usize::from(cover.is_some());
+    let chapters = cover.iter().chain(chapters).collect::<Vec<_>>();
 
-        // Calculate scale factor to fit width
-        let scale_factor = available_width / img_width_mm;
-        trace!("Image dimensions: {}x{}, scale factor: {:.2}",
-              img_data.width(), img_data.height(), scale_factor);
+    debug!("Creating PDF from {} images in {} chapters with layout {:?}", image_count, chapters.len(), options.layout);
+    trace!("Output path: {:?}", output_path);
 
-        // Create and add the image with proper scaling
-        let img = genpdf::elements::Image::from_path(path)
-            .map_err(|e| DownloadError::ImageProcessingError(format!("Failed to load image: {}", e)))?
-            .with_alignment(genpdf::Alignment::Center)
-            .with_scale(genpdf::Scale::new(scale_factor, scale_factor));
+    let mut pdf = PdfStream::new(BufWriter::new(File::create(output_path)?))?;
+    let mut next_ref = Ref::new(1);
+    let mut alloc = || next_ref.bump();
+    let catalog_id = alloc();
+    let page_tree_id = alloc();
+    let mut page_ids = Vec::new();
+    // Bookmark title and the first page of every titled chapter
+    let mut bookmarks = Vec::new();
+
+    let pages = chapters.iter().flat_map(|chapter| {
+        chapter.image_paths.iter().enumerate().map(move |(i, path)| (chapter, i == 0, path))
+    });
+    for (i, (chapter, first_page, path)) in pages.enumerate() {
+        trace!("Processing image {}/{}", i + 1, image_count);
+
+        // The image is embedded once and shared by every page showing a slice of it
+        let image_id = alloc();
+        let (image_width, image_height) = write_image(&mut pdf, image_id, path)?;
+        let placements = layout_pages(image_width, image_height, options);
+        trace!("Image dimensions: {}x{}, {} page(s)", image_width, image_height, placements.len());
+
+        for (slice, placement) in placements.into_iter().enumerate() {
+            let page_id = alloc();
+            if first_page && slice == 0 && !chapter.title.is_empty() {
+                bookmarks.push((chapter.title.as_str(), page_id, placement.page_height));
+            }
 
-        doc.push(img);
+            let content_id = alloc();
+            let image_name = Name(b"Im1");
+
+            pdf.write_object(page_id, |chunk| {
+                let mut page = chunk.page(page_id);
+                page.media_box(Rect::new(0.0, 0.0, placement.page_width, placement.page_height));
+                page.parent(page_tree_id);
+                page.contents(content_id);
+                page.resources().x_objects().pair(image_name, image_id);
+            })?;
+
+            let [x, y, width, height] = placement.image;
+            let mut content = Content::new();
+            content.save_state();
+            if let Some([clip_x, clip_y, clip_width, clip_height]) = placement.clip {
+                content.rect(clip_x, clip_y, clip_width, clip_height).clip_nonzero().end_path();
+            }
+            content.transform([width, 0.0, 0.0, height, x, y]);
+            content.x_object(image_name);
+            content.restore_state();
+            let content = content.finish();
+            pdf.write_object(content_id, |chunk| {
+                chunk.stream(content_id, &content);
+            })?;
+
+            page_ids.push(page_id);
+        }
+    }
 
-        // Add a page break after each image except the last one
-        if i < image_paths.len() - 1 {
-            doc.push(genpdf::elements::PageBreak::new());
+    pdf.write_object(page_tree_id, |chunk| {
+        chunk.pages(page_tree_id).kids(page_ids.iter().copied()).count(page_ids.len() as i32);
+    })?;
+
+    let outline_id = (!bookmarks.is_empty()).then(&mut alloc);
+    pdf.write_object(catalog_id, |chunk| {
+        let mut catalog = chunk.indirect(catalog_id).start::<Catalog>();
+        catalog.pages(page_tree_id);
+        if let Some(outline_id) = outline_id {
+            catalog.outlines(outline_id);
+            catalog.page_mode(PageMode::UseOutlines);
         }
+    })?;
+
+    if let Some(outline_id) = outline_id {
+        write_outline(&mut pdf, outline_id, &bookmarks, &mut alloc)?;
     }
+    let info_id = alloc();
+    write_metadata(&mut pdf, info_id, metadata)?;
 
-    // Render the PDF to file
-    debug!("Rendering PDF to file: {:?}", output_path);
-    doc.render_to_file(output_path)?;
-    info!("PDF created successfully with {} pages", image_paths.len());
+    pdf.finish(catalog_id, info_id)?;
+    info!("PDF created successfully with {} pages from {} images", page_ids.len(), image_count);
 
     Ok(())
 }
 
-/// Finds a suitable system font with cross-platform support
-fn find_system_font() -> Result<genpdf::fonts::FontFamily<genpdf::fonts::FontData>, String> {
-    debug!("Looking for suitable font");
+/// Writes PDF objects to the output as soon as they are complete
+///
+/// Only the offsets needed for the cross-reference table are kept in memory.
+struct PdfStream<W: Write> {
+    out: W,
+    position: usize,
+    offsets: Vec<(i32, usize)>,
+}
 
-    // First try the embedded Roboto font which should be reliable
-    if let Ok(font_family) = create_embedded_roboto_font() {
-        info!("Using embedded Roboto font");
-        return Ok(font_family);
+impl<W: Write> PdfStream<W> {
+    fn new(mut out: W) -> io::Result<Self> {
+        const HEADER: &[u8] = b"%PDF-1.7\n%\x80\x80\x80\x80\n\n";
+        out.write_all(HEADER)?;
+        Ok(Self { out, position: HEADER.len(), offsets: Vec::new() })
     }
 
-    // Then try with direct font paths that are known to work well
-    if let Ok(font_family) = load_direct_system_font() {
-        info!("Using direct system font");
-        return Ok(font_family);
-    }
+    /// Write the indirect object `id`, which `write` must add to the given chunk
+    fn write_object(&mut self, id: Ref, write: impl FnOnce(&mut Chunk)) -> io::Result<()> {
+        let mut chunk = Chunk::new();
+        write(&mut chunk);
 
-    // Next, try to load from system font locations
-    if let Ok(font_family) = find_system_font_from_paths() {
-        info!("Using system font");
-        return Ok(font_family);
+        self.offsets.push((id.get(), self.position));
+        self.out.write_all(chunk.as_bytes())?;
+        self.position += chunk.len();
+        Ok(())
     }
 
-    // Then try to load from the bundled font file
-    if let Ok(font_family) = load_bundled_font_from_file() {
-        info!("Using bundled font file");
-        return Ok(font_family);
-    }
+    /// Write the cross-reference table and trailer
+    fn finish(mut self, catalog_id: Ref, info_id: Ref) -> io::Result<()> {
+        self.offsets.sort_unstable();
+        let size = self.offsets.last().map_or(0, |(id, _)| *id) + 1;
+
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f\r\n", size);
+        let mut offsets = self.offsets.iter().peekable();
+        for id in 1..size {
+            match offsets.next_if(|(object_id, _)| *object_id == id) {
+                Some((_, offset)) => xref.push_str(&format!("{:010} 00000 n\r\n", offset)),
+                None => xref.push_str("0000000000 65535 f\r\n"),
+            }
+        }
+        xref.push_str(&format!(
+            "trailer\n<<\n  /Size {}\n  /Root {} 0 R\n  /Info {} 0 R\n>>\nstartxref\n{}\n%%EOF",
+            size, catalog_id.get(), info_id.get(), self.position
+        ));
 
-    warn!("Could not load any suitable font");
-    Err("Could not load any suitable font".to_string())
+        self.out.write_all(xref.as_bytes())?;
+        self.out.flush()
+    }
 }
 
-/// Create a font family using the embedded Roboto font
-fn create_embedded_roboto_font() -> Result<genpdf::fonts::FontFamily<genpdf::fonts::FontData>, String> {
-    trace!("Attempting to use embedded Roboto font");
-
-    // Check if Geneva.ttf exists on macOS and use it directly (known to work)
-    if env::consts::OS == "macos" {
-        let geneva_path = Path::new("/System/Library/Fonts/Geneva.ttf");
-        if geneva_path.exists() {
-            debug!("Found Geneva.ttf on macOS: {:?}", geneva_path);
-            if let Ok(bytes) = fs::read(geneva_path) {
-                match genpdf::fonts::FontData::new(bytes, None) {
-                    Ok(font_data) => {
-                        info!("Using system Geneva font as embedded fallback");
-                        let regular = font_data.clone();
-                        let bold = font_data.clone();
-                        let italic = font_data.clone();
-                        let bold_italic = font_data;
-
-                        return Ok(genpdf::fonts::FontFamily {
-                            regular,
-                            bold,
-                            italic,
-                            bold_italic,
-                        });
-                    },
-                    Err(e) => {
-                        debug!("Failed to load Geneva font: {}", e);
-                    } // Continue to try other fonts
-                }
+/// Compute the pages an image of the given pixel size is laid out on
+///
+/// Images too tall for one page are split into several pages showing consecutive slices.
+pub fn layout_pages(image_width: u32, image_height: u32, options: &PdfOptions) -> Vec<PagePlacement> {
+    let (image_width, image_height) = (image_width.max(1) as f32, image_height.max(1) as f32);
+    let (paper_width, paper_height) = options.paper_size.dimensions();
+
+    match options.layout {
+        PdfLayout::ImageSize => {
+            // One pixel per point, scaled down only if the image is wider than a PDF page may be
+            let scale = (MAX_PAGE_SIZE / image_width).min(1.0);
+            slice_pages(image_width * scale, image_height * scale, MAX_PAGE_SIZE, 0.0, None)
+        }
+        PdfLayout::FitWidth => {
+            let scale = paper_width / image_width;
+            slice_pages(paper_width, image_height * scale, paper_height, 0.0, None)
+        }
+        PdfLayout::FitPage => {
+            // Double-page spreads go on landscape paper instead of being squashed
+            let (paper_width, paper_height) = if image_width > image_height {
+                (paper_height, paper_width)
+            } else {
+                (paper_width, paper_height)
+            };
+            let margin = (options.margin_mm * POINTS_PER_MM).min(paper_width / 4.0);
+            let available_width = paper_width - 2.0 * margin;
+            let available_height = paper_height - 2.0 * margin;
+
+            let width_scale = available_width / image_width;
+            if image_height * width_scale > available_height * TALL_IMAGE_PAGES {
+                return slice_pages(image_width * width_scale, image_height * width_scale, available_height, margin, Some((paper_width, paper_height)));
             }
+
+            let scale = width_scale.min(available_height / image_height);
+            let (width, height) = (image_width * scale, image_height * scale);
+            vec![PagePlacement {
+                page_width: paper_width,
+                page_height: paper_height,
+                image: [(paper_width - width) / 2.0, (paper_height - height) / 2.0, width, height],
+                clip: None,
+            }]
         }
     }
+}
 
-    // These bytes are directly embedded in the binary
-    let font_bytes = include_bytes!("assets/fonts/Roboto-Regular.ttf");
+/// Split an image drawn at `width` x `height` points into pages showing at most `slice_height` of it
+///
+/// With a fixed `paper` size every page has that size and slices start `margin` from the top;
+/// otherwise each page is as tall as its slice.
+fn slice_pages(width: f32, height: f32, slice_height: f32, margin: f32, paper: Option<(f32, f32)>) -> Vec<PagePlacement> {
+    // Ignore rounding leftovers so a tiny sliver doesn't get a page of its own
+    let count = ((height - 0.5) / slice_height).ceil().max(1.0) as usize;
+
+    (0..count).map(|slice| {
+        let offset = slice as f32 * slice_height;
+        let visible = (height - offset).min(slice_height);
+        let (page_width, page_height) = paper.unwrap_or((width + 2.0 * margin, visible + 2.0 * margin));
+        let top = page_height - margin;
+        let x = (page_width - width) / 2.0;
+
+        PagePlacement {
+            page_width,
+            page_height,
+            image: [x, top + offset - height, width, height],
+            clip: (count > 1).then_some([x, top - visible, width, visible]),
+        }
+    }).collect()
+}
 
-    if font_bytes.len() < 1000 {
-        warn!("Embedded Roboto font bytes appear to be incomplete");
-        return Err("Embedded Roboto font bytes appear to be incomplete".to_string());
+/// Write a flat outline with one bookmark per chapter, each opening at the top of its first page
+fn write_outline<W: Write>(pdf: &mut PdfStream<W>, outline_id: Ref, bookmarks: &[(&str, Ref, f32)], alloc: &mut impl FnMut() -> Ref) -> io::Result<()> {
+    let item_ids = bookmarks.iter().map(|_| alloc()).collect::<Vec<_>>();
+
+    pdf.write_object(outline_id, |chunk| {
+        chunk.outline(outline_id)
+            .first(item_ids[0])
+            .last(item_ids[item_ids.len() - 1])
+            .count(item_ids.len() as i32);
+    })?;
+
+    for (i, (title, page_id, page_height)) in bookmarks.iter().enumerate() {
+        pdf.write_object(item_ids[i], |chunk| {
+            let mut item = chunk.outline_item(item_ids[i]);
+            item.title(TextStr(title));
+            item.parent(outline_id);
+            if i > 0 {
+                item.prev(item_ids[i - 1]);
+            }
+            if let Some(next) = item_ids.get(i + 1) {
+                item.next(*next);
+            }
+            item.dest().page(*page_id).xyz(0.0, *page_height, None);
+        })?;
     }
 
-    match genpdf::fonts::FontData::new(font_bytes.to_vec(), None) {
-        Ok(font_data) => {
-            debug!("Successfully loaded embedded Roboto font");
-            // Use the same font for all styles
-            let regular = font_data.clone();
-            let bold = font_data.clone();
-            let italic = font_data.clone();
-            let bold_italic = font_data;
-
-            Ok(genpdf::fonts::FontFamily {
-                regular,
-                bold,
-                italic,
-                bold_italic,
-            })
-        },
-        Err(e) => {
-            warn!("Failed to load embedded Roboto font: {}", e);
-            Err(format!("Failed to load embedded Roboto font: {}", e))
-        },
-    }
+    Ok(())
 }
 
-/// Try to load a font that is known to work well with genpdf
-fn load_direct_system_font() -> Result<genpdf::fonts::FontFamily<genpdf::fonts::FontData>, String> {
-    let os = env::consts::OS;
-    debug!("Attempting to load direct system font for OS: {}", os);
-
-    // Prioritize TTF files which work better with rusttype than TTC files
-    let font_paths = match os {
-        "macos" => vec![
-            // TTF files first (these work better with rusttype)
-            "/System/Library/Fonts/Geneva.ttf",
-            "/System/Library/Fonts/Monaco.ttf",
-            // TTC files as fallbacks
-            "/System/Library/Fonts/Times.ttc",
-            "/System/Library/Fonts/Helvetica.ttc",
-            "/System/Library/Fonts/LucidaGrande.ttc",
-        ],
-        "windows" => vec![
-            "C:\\Windows\\Fonts\\arial.ttf",
-            "C:\\Windows\\Fonts\\verdana.ttf",
-            "C:\\Windows\\Fonts\\tahoma.ttf",
-            "C:\\Windows\\Fonts\\times.ttf",
-            "C:\\Windows\\Fonts\\calibri.ttf",
-        ],
-        "linux" => vec![
-            "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
-            "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
-            "/usr/share/fonts/TTF/DejaVuSans.ttf",
-        ],
-        _ => vec![],
-    };
+/// Write the document information dictionary
+fn write_metadata<W: Write>(pdf: &mut PdfStream<W>, id: Ref, metadata: &PdfMetadata) -> io::Result<()> {
+    let keywords = metadata.keywords.join(", ");
 
-    for path in font_paths {
-        // Skip TTC files if they're likely to cause problems
-        if path.ends_with(".ttc") && (path != "/System/Library/Fonts/Geneva.ttf") {
-            trace!("Skipping TTC file: {}", path);
-            continue; // Skip TTC files as they often fail with rusttype
+    pdf.write_object(id, |chunk| {
+        let mut info = chunk.indirect(id).start::<DocumentInfo>();
+        if let Some(ref title) = metadata.title {
+            info.title(TextStr(title));
         }
-
-        trace!("Trying font: {}", path);
-        if let Ok(bytes) = fs::read(path) {
-            match genpdf::fonts::FontData::new(bytes.clone(), None) {
-                Ok(font_data) => {
-                    info!("Successfully loaded font: {}", path);
-                    // Create a font family with all styles using the same font
-                    return Ok(genpdf::fonts::FontFamily {
-                        regular: font_data.clone(),
-                        bold: font_data.clone(),
-                        italic: font_data.clone(),
-                        bold_italic: font_data,
-                    });
-                },
-                Err(e) => {
-                    trace!("Failed to load font {}: {}", path, e);
-                    // Don't log every failure as it's normal to try multiple fonts
-                    // before finding one that works
-                }
-            }
-        } else {
-            trace!("Font file not found or not readable: {}", path);
+        if let Some(ref author) = metadata.author {
+            info.author(TextStr(author));
         }
-    }
-
-    debug!("No direct system font found");
-    Err("No direct system font found".to_string())
+        if let Some(ref subject) = metadata.subject {
+            info.subject(TextStr(subject));
+        }
+        if !keywords.is_empty() {
+            info.keywords(TextStr(&keywords));
+        }
+        info.creator(TextStr(concat!("download-manga ", env!("CARGO_PKG_VERSION"))));
+    })
 }
 
-/// Try to locate a system font from various paths
-fn find_system_font_from_paths() -> Result<genpdf::fonts::FontFamily<genpdf::fonts::FontData>, String> {
-    debug!("Searching for system fonts in various paths");
-    // Common font locations by platform
-    let font_paths: Vec<(String, &str)> = get_platform_font_paths();
-
-    // Try each font path
-    for (path, font) in font_paths {
-        let font_path = Path::new(&path).join(font);
-        trace!("Checking font path: {:?}", font_path);
-
-        if font_path.exists() {
-            debug!("Found font at: {}", font_path.display());
-            // Try directly first
-            if let Ok(bytes) = fs::read(&font_path) {
-                if let Ok(font_data) = genpdf::fonts::FontData::new(bytes, None) {
-                    debug!("Successfully loaded font data directly");
-                    let regular = font_data.clone();
-                    let bold = font_data.clone();
-                    let italic = font_data.clone();
-                    let bold_italic = font_data;
-
-                    return Ok(genpdf::fonts::FontFamily {
-                        regular,
-                        bold,
-                        italic,
-                        bold_italic,
-                    });
-                } else {
-                    trace!("Failed to create font data from bytes for: {}", font_path.display());
-                }
+/// Embed the image at `path` as an XObject and return its size in pixels
+///
+/// JPEG files are embedded as they are (DCTDecode); other formats are decoded and
+/// Flate-compressed, in grayscale when the source has no color.
+fn write_image<W: Write>(pdf: &mut PdfStream<W>, id: Ref, path: &Path) -> Result<(u32, u32), DownloadError> {
+    let data = fs::read(path)?;
+
+    if let Some(jpeg) = jpeg_info(&data) {
+        trace!("Embedding JPEG {:?} without re-encoding", path);
+        pdf.write_object(id, |chunk| {
+            let mut xobject = chunk.image_xobject(id, &data);
+            xobject.filter(Filter::DctDecode);
+            xobject.width(jpeg.width as i32);
+            xobject.height(jpeg.height as i32);
+            if jpeg.components == 1 {
+                xobject.color_space().device_gray();
             } else {
-                trace!("Failed to read font file: {}", font_path.display());
-            }
-
-            // Try the normal way
-            match genpdf::fonts::from_files(&path, font, None) {
-                Ok(font_family) => {
-                    debug!("Successfully loaded font family via genpdf API");
-                    return Ok(font_family);
-                },
-                Err(e) => {
-                    trace!("Failed to load font via genpdf API: {}", e);
-                }
+                xobject.color_space().device_rgb();
             }
-        }
+            xobject.bits_per_component(8);
+        })?;
+        return Ok((jpeg.width, jpeg.height));
     }
 
-    debug!("No system font found in searched paths");
-    Err("No system font found".to_string())
-}
+    let image = image::load_from_memory(&data)
+        .map_err(|e| DownloadError::PdfGenerationError(format!("Failed to load image {:?}: {}", path, e)))?;
+    drop(data);
+
+    let grayscale = !image.color().has_color();
+    let pixels = if grayscale {
+        image.to_luma8().into_raw()
+    } else {
+        image.to_rgb8().into_raw()
+    };
 
-/// Try to load the bundled font from the assets directory
-fn load_bundled_font_from_file() -> Result<genpdf::fonts::FontFamily<genpdf::fonts::FontData>, String> {
-    let bundled_font_path = Path::new("assets/fonts/LiberationSans-Regular.ttf");
-    if bundled_font_path.exists() {
-        println!("Found bundled font at: {}", bundled_font_path.display());
-        // Try to read the file directly
-        match fs::read(bundled_font_path) {
-            Ok(bytes) => {
-                if bytes.len() < 100 {
-                    return Err("Bundled font file is too small or corrupt".to_string());
-                }
-
-                match genpdf::fonts::FontData::new(bytes, None) {
-                    Ok(font_data) => {
-                        let regular = font_data.clone();
-                        let bold = font_data.clone();
-                        let italic = font_data.clone();
-                        let bold_italic = font_data;
-
-                        return Ok(genpdf::fonts::FontFamily {
-                            regular,
-                            bold,
-                            italic,
-                            bold_italic,
-                        });
-                    },
-                    Err(e) => return Err(format!("Could not create font data: {}", e)),
-                }
-            },
-            Err(e) => return Err(format!("Could not read bundled font file: {}", e)),
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&pixels)?;
+    let compressed = encoder.finish()?;
+    drop(pixels);
+
+    pdf.write_object(id, |chunk| {
+        let mut xobject = chunk.image_xobject(id, &compressed);
+        xobject.filter(Filter::FlateDecode);
+        xobject.width(image.width() as i32);
+        xobject.height(image.height() as i32);
+        if grayscale {
+            xobject.color_space().device_gray();
+        } else {
+            xobject.color_space().device_rgb();
         }
-    }
+        xobject.bits_per_component(8);
+    })?;
 
-    Err("Bundled font file does not exist".to_string())
+    Ok((image.width(), image.height()))
 }
 
-/// Returns a list of platform-specific font paths to try
-fn get_platform_font_paths() -> Vec<(String, &'static str)> {
-    let mut paths = Vec::new();
-
-    // Detect operating system
-    let os = env::consts::OS;
-    println!("Detected OS: {}", os);
-
-    match os {
-        "macos" => {
-            // Prioritize TTF files first
-            paths.push(("/System/Library/Fonts".to_string(), "Geneva.ttf"));
-            paths.push(("/System/Library/Fonts".to_string(), "Monaco.ttf"));
-            // Then try TTC files
-            paths.push(("/System/Library/Fonts".to_string(), "Helvetica.ttc"));
-            paths.push(("/Library/Fonts".to_string(), "Arial.ttf"));
-            paths.push(("/System/Library/Fonts".to_string(), "LucidaGrande.ttc"));
-            paths.push(("/System/Library/Fonts".to_string(), "Times.ttc"));
-            paths.push(("/System/Library/Fonts".to_string(), "Menlo.ttc"));
-            paths.push(("/System/Library/Fonts".to_string(), "AppleSDGothicNeo.ttc"));
-        },
-        "windows" => {
-            paths.push(("C:\\Windows\\Fonts".to_string(), "arial.ttf"));
-            paths.push(("C:\\Windows\\Fonts".to_string(), "times.ttf"));
-            paths.push(("C:\\Windows\\Fonts".to_string(), "cour.ttf"));
-            paths.push(("C:\\Windows\\Fonts".to_string(), "tahoma.ttf"));
-            paths.push(("C:\\Windows\\Fonts".to_string(), "verdana.ttf"));
-            paths.push(("C:\\Windows\\Fonts".to_string(), "calibri.ttf"));
-            paths.push(("C:\\Windows\\Fonts".to_string(), "segoeui.ttf"));
-        },
-        "linux" => {
-            // Common Linux font paths
-            paths.push(("/usr/share/fonts/truetype/dejavu".to_string(), "DejaVuSans.ttf"));
-            paths.push(("/usr/share/fonts/TTF".to_string(), "Arial.ttf"));
-            paths.push(("/usr/share/fonts/truetype/liberation".to_string(), "LiberationSans-Regular.ttf"));
-            paths.push(("/usr/share/fonts/truetype/ubuntu".to_string(), "Ubuntu-R.ttf"));
-            paths.push(("/usr/share/fonts/liberation".to_string(), "LiberationSans-Regular.ttf"));
-            paths.push(("/usr/share/fonts/TTF".to_string(), "DejaVuSans.ttf"));
-            paths.push(("/usr/share/fonts/opentype".to_string(), "SourceSansPro-Regular.otf"));
-            paths.push(("/usr/share/fonts/noto".to_string(), "NotoSans-Regular.ttf"));
-            paths.push(("/usr/share/fonts/truetype/noto".to_string(), "NotoSans-Regular.ttf"));
-        },
-        _ => {
-            // Add some reasonable defaults for other platforms
-            paths.push(("/usr/local/share/fonts".to_string(), "Arial.ttf"));
-        }
-    }
+/// Size and color layout of a JPEG that PDF readers can decode directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct JpegInfo {
+    width: u32,
+    height: u32,
+    components: u8,
+}
 
-    // Also check user's home directory for fonts
-    if let Ok(home) = home_dir() {
-        match os {
-            "macos" => {
-                let user_font = home.join("Library/Fonts");
-                paths.push((user_font.to_string_lossy().to_string(), "Arial.ttf"));
-                paths.push((user_font.to_string_lossy().to_string(), "Helvetica.ttf"));
-            },
-            "windows" => {
-                let user_font = home.join("AppData\\Local\\Microsoft\\Windows\\Fonts");
-                paths.push((user_font.to_string_lossy().to_string(), "arial.ttf"));
-                paths.push((user_font.to_string_lossy().to_string(), "calibri.ttf"));
-            },
-            "linux" => {
-                let user_font = home.join(".local/share/fonts");
-                paths.push((user_font.to_string_lossy().to_string(), "DejaVuSans.ttf"));
-                paths.push((user_font.to_string_lossy().to_string(), "LiberationSans-Regular.ttf"));
-            },
-            _ => {}
-        }
+/// Read the frame header of a JPEG without decoding it
+///
+/// Returns `None` for anything that isn't an 8-bit baseline or progressive JPEG in
+/// grayscale or RGB, so such files are re-encoded instead.
+fn jpeg_info(data: &[u8]) -> Option<JpegInfo> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
     }
 
-    // Always add the local project font directory
-    paths.push(("assets/fonts".to_string(), "LiberationSans-Regular.ttf"));
-    paths.push(("assets/fonts".to_string(), "Roboto-Regular.ttf"));
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // Fill bytes and markers without a length
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD8).contains(&marker) {
+            pos += 2;
+            continue;
+        }
+        // Reached the image data or the end without finding a frame header
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
 
-    paths
-}
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            // Only Huffman-coded baseline, extended and progressive frames are supported everywhere
+            if marker > 0xC2 {
+                return None;
+            }
+            let frame = data.get(pos + 4..pos + 10)?;
+            let info = JpegInfo {
+                height: u32::from(u16::from_be_bytes([frame[1], frame[2]])),
+                width: u32::from(u16::from_be_bytes([frame[3], frame[4]])),
+                components: frame[5],
+            };
+            let supported = frame[0] == 8 && info.width > 0 && info.height > 0 && matches!(info.components, 1 | 3);
+            return supported.then_some(info);
+        }
 
-/// Cross-platform function to get the home directory
-fn home_dir() -> Result<std::path::PathBuf, String> {
-    dirs::home_dir().ok_or_else(|| "Could not determine home directory".to_string())
-}
+        pos += 2 + length;
+    }
 
-// Helper function to load an image from a path
-fn load_image_from_path(path: impl AsRef<Path>) -> Result<image::DynamicImage, DownloadError> {
-    image::open(path.as_ref())
-        .map_err(|e| DownloadError::PdfGenerationError(format!("Failed to load image: {}", e)))
+    None
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
-    use std::io::Write;
 
     // Helper to create a temporary test image
     fn create_test_image(path: &Path, width: u32, height: u32) -> Result<(), DownloadError> {
@@ -422,15 +526,15 @@ mod tests {
         // Fill with a simple pattern
         for (x, y, pixel) in img.enumerate_pixels_mut() {
             *pixel = image::Rgb([
-                ((x as u32) % 256) as u8,
-                ((y as u32) % 256) as u8,
-                ((x + y) as u32 % 256) as u8,
+                (x % 256) as u8,
+                (y % 256) as u8,
+                ((x + y) % 256) as u8,
             ]);
         }
 
         // Save it
         if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent).map_err(|e| DownloadError::IoError(e))?;
+            fs::create_dir_all(parent).map_err(DownloadError::IoError)?;
         }
 
         img.save(path).map_err(|e|
@@ -440,6 +544,10 @@ mod tests {
         Ok(())
     }
 
+    fn options(layout: PdfLayout) -> PdfOptions {
+        PdfOptions { layout, ..PdfOptions::default() }
+    }
+
     #[test]
     fn test_create_pdf_from_images() {
         // Create a temporary directory for the test
@@ -472,7 +580,166 @@ mod tests {
         let metadata = fs::metadata(&output_path).unwrap();
         assert!(metadata.len() > 100); // Make sure it's not empty
 
+        let content = fs::read(&output_path).unwrap();
+        assert!(content.starts_with(b"%PDF-"));
+        assert!(String::from_utf8_lossy(&content).contains("/Count 3"));
+
         // Clean up
         let _ = fs::remove_dir_all(temp_dir);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_create_volume_pdf_with_outline_and_metadata() {
+        let temp_dir = std::env::temp_dir().join("manga_pdf_volume_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let pages = [temp_dir.join("1.png"), temp_dir.join("2.png"), temp_dir.join("3.png")];
+        for page in &pages {
+            create_test_image(page, 60, 90).unwrap();
+        }
+
+        let chapters = vec![
+            PdfChapter { title: String::from("Chapter 1"), image_paths: pages[..2].to_vec() },
+            PdfChapter { title: String::from("Chapter 2"), image_paths: pages[2..].to_vec() },
+        ];
+        let metadata = PdfMetadata {
+            title: Some(String::from("Example - Volume 1")),
+            author: Some(String::from("Jane Doe")),
+            subject: None,
+            keywords: vec![String::from("Action"), String::from("Comedy")],
+        };
+        let output_path = temp_dir.join("volume.pdf");
+        create_volume_pdf(&chapters, &output_path, &PdfOptions::default(), &metadata).unwrap();
+
+        let content = String::from_utf8_lossy(&fs::read(&output_path).unwrap()).into_owned();
+        assert!(content.contains("/Count 3"));
+        assert!(content.contains("/Type /Outlines"));
+        assert!(content.contains("/Title (Chapter 1)"));
+        assert!(content.contains("/Title (Chapter 2)"));
+        assert!(content.contains("/PageMode /UseOutlines"));
+        assert!(content.contains("/Title (Example - Volume 1)"));
+        assert!(content.contains("/Author (Jane Doe)"));
+        assert!(content.contains("/Keywords (Action, Comedy)"));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn test_cover_is_the_first_page() {
+        let temp_dir = std::env::temp_dir().join("manga_pdf_cover_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let cover = temp_dir.join("cover.png");
+        let page = temp_dir.join("1.png");
+        create_test_image(&cover, 60, 90).unwrap();
+        create_test_image(&page, 60, 90).unwrap();
+
+        let options = PdfOptions { cover: Some(cover), ..PdfOptions::default() };
+        let chapters = vec![PdfChapter { title: String::from("Chapter 1"), image_paths: vec![page] }];
+        let output_path = temp_dir.join("chapter.pdf");
+        create_volume_pdf(&chapters, &output_path, &options, &PdfMetadata::default()).unwrap();
+
+        let content = String::from_utf8_lossy(&fs::read(&output_path).unwrap()).into_owned();
+        assert!(content.contains("/Count 2"));
+        // Only the chapter is bookmarked
+        assert!(content.contains("/Count 1"));
+
+        // A cover alone doesn't make a PDF
+        assert!(create_volume_pdf(&[], &output_path, &options, &PdfMetadata::default()).is_err());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn test_jpeg_pages_are_embedded_without_re_encoding() {
+        let temp_dir = std::env::temp_dir().join("manga_pdf_jpeg_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let jpeg = temp_dir.join("page.jpg");
+        let png = temp_dir.join("page.png");
+        create_test_image(&jpeg, 120, 180).unwrap();
+        create_test_image(&png, 120, 180).unwrap();
+
+        let output_path = temp_dir.join("jpeg.pdf");
+        create_pdf_from_images(&[jpeg.clone(), png], &output_path).unwrap();
+
+        let content = fs::read(&output_path).unwrap();
+        let jpeg_data = fs::read(&jpeg).unwrap();
+        let text = String::from_utf8_lossy(&content);
+        assert!(text.contains("/DCTDecode"));
+        assert!(text.contains("/FlateDecode"));
+        assert!(content.windows(jpeg_data.len()).any(|window| window == jpeg_data));
+
+        // The trailer must point at the cross-reference table
+        let startxref = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let offset = text[startxref..].lines().next().unwrap().parse::<usize>().unwrap();
+        assert!(content[offset..].starts_with(b"xref"));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn test_jpeg_info() {
+        let mut jpeg = Vec::new();
+        image::DynamicImage::ImageLuma8(image::GrayImage::new(33, 17))
+            .write_to(&mut io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+
+        assert_eq!(jpeg_info(&jpeg), Some(JpegInfo { width: 33, height: 17, components: 1 }));
+        assert_eq!(jpeg_info(b"\x89PNG\r\n\x1a\n"), None);
+        // A CMYK frame header is left to the re-encoding path
+        assert_eq!(jpeg_info(&[0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x11, 8, 0, 10, 0, 10, 4]), None);
+    }
+
+    #[test]
+    fn test_fit_page_keeps_margins_and_aspect_ratio() {
+        let (paper_width, paper_height) = PaperSize::A4.dimensions();
+        let pages = layout_pages(800, 1200, &options(PdfLayout::FitPage));
+
+        assert_eq!(pages.len(), 1);
+        let [x, y, width, height] = pages[0].image;
+        assert_eq!((pages[0].page_width, pages[0].page_height), (paper_width, paper_height));
+        assert!(x >= 6.0 * POINTS_PER_MM - 0.01 && y >= 6.0 * POINTS_PER_MM - 0.01);
+        assert!((width / height - 800.0 / 1200.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_fit_page_turns_paper_for_spreads() {
+        let pages = layout_pages(2000, 1400, &options(PdfLayout::FitPage));
+        assert!(pages[0].page_width > pages[0].page_height);
+    }
+
+    #[test]
+    fn test_image_size_matches_image() {
+        let pages = layout_pages(700, 1000, &options(PdfLayout::ImageSize));
+
+        assert_eq!(pages.len(), 1);
+        assert_eq!((pages[0].page_width, pages[0].page_height), (700.0, 1000.0));
+        assert_eq!(pages[0].image, [0.0, 0.0, 700.0, 1000.0]);
+        assert_eq!(pages[0].clip, None);
+    }
+
+    #[test]
+    fn test_tall_images_are_split() {
+        // A webtoon strip: 800 px wide, 20 pages of paper tall at that width
+        let (paper_width, paper_height) = PaperSize::A4.dimensions();
+        let strip_height = (paper_height / paper_width * 800.0 * 20.0) as u32;
+
+        let pages = layout_pages(800, strip_height, &options(PdfLayout::FitWidth));
+        assert_eq!(pages.len(), 20);
+        for page in &pages {
+            assert_eq!(page.page_width, paper_width);
+            assert!(page.clip.is_some());
+        }
+        // The first slice shows the top of the image, the last one its bottom
+        assert!((pages[0].image[1] + pages[0].image[3] - pages[0].page_height).abs() < 0.5);
+        assert!(pages[19].image[1].abs() < 0.5);
+
+        let pages = layout_pages(800, strip_height, &options(PdfLayout::FitPage));
+        assert!(pages.len() > 1);
+        assert!(pages.iter().all(|page| page.page_height == paper_height && page.clip.is_some()));
+
+        let pages = layout_pages(800, 30_000, &options(PdfLayout::ImageSize));
+        assert_eq!(pages.len(), 3);
+        assert!(pages.iter().all(|page| page.page_height <= MAX_PAGE_SIZE));
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use image::{GrayImage, Luma};
+use log::{debug, warn};
+use rusttype::{point, Font, Scale};
+
+use crate::error::DownloadError;
+
+const BACKGROUND: u8 = 240;
+const FOREGROUND: u8 = 40;
+
+/// System fonts tried when the embedded font can't be loaded
+const FALLBACK_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/System/Library/Fonts/Geneva.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// Renders a page telling the reader that page `page_number` could not be downloaded
+///
+/// The image format is picked from the extension of `path`. Without any usable font the
+/// page is left blank rather than failing the chapter.
+pub fn create_missing_page(path: &Path, page_number: usize, width: u32, height: u32) -> Result<(), DownloadError> {
+    let mut page = GrayImage::from_pixel(width, height, Luma([BACKGROUND]));
+
+    match load_font() {
+        Some(font) => {
+            let heading_size = width as f32 / 10.0;
+            let center = height as f32 / 2.0;
+
+            draw_centered_text(&mut page, &font, &format!("Page {} missing", page_number), heading_size, center - heading_size * 0.6);
+            draw_centered_text(&mut page, &font, "This page could not be downloaded", heading_size / 2.5, center + heading_size * 0.6);
+        }
+        None => warn!("No usable font found, placeholder for page {} will be blank", page_number),
+    }
+
+    page.save(path)
+        .map_err(|e| DownloadError::ImageProcessingError(format!("Failed to save placeholder page: {}", e)))
+}
+
+/// Load the embedded Roboto font, falling back to common system fonts
+fn load_font() -> Option<Font<'static>> {
+    if let Some(font) = Font::try_from_bytes(include_bytes!("assets/fonts/Roboto-Regular.ttf")) {
+        return Some(font);
+    }
+    debug!("Embedded Roboto font is unusable, trying system fonts");
+
+    FALLBACK_FONTS.iter()
+        .filter_map(|path| std::fs::read(path).ok())
+        .find_map(Font::try_from_vec)
+}
+
+/// Draws a single line of text horizontally centered, with its vertical center at `center_y`
+fn draw_centered_text(page: &mut GrayImage, font: &Font, text: &str, size: f32, center_y: f32) {
+    let scale = Scale::uniform(size);
+    let v_metrics = font.v_metrics(scale);
+    let glyphs = font.layout(text, scale, point(0.0, v_metrics.ascent)).collect::<Vec<_>>();
+
+    let text_width = glyphs.iter()
+        .filter_map(|glyph| glyph.pixel_bounding_box())
+        .map(|bounds| bounds.max.x)
+        .max()
+        .unwrap_or(0);
+    let offset_x = (page.width() as i32 - text_width) / 2;
+    let offset_y = (center_y - (v_metrics.ascent - v_metrics.descent) / 2.0) as i32;
+
+    for glyph in glyphs {
+        let Some(bounds) = glyph.pixel_bounding_box() else {
+            continue;
+        };
+
+        glyph.draw(|x, y, coverage| {
+            let px = offset_x + bounds.min.x + x as i32;
+            let py = offset_y + bounds.min.y + y as i32;
+            if px < 0 || py < 0 || px >= page.width() as i32 || py >= page.height() as i32 {
+                return;
+            }
+
+            let value = f32::from(BACKGROUND) * (1.0 - coverage) + f32::from(FOREGROUND) * coverage;
+            page.put_pixel(px as u32, py as u32, Luma([value as u8]));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_missing_page() {
+        let path = std::env::temp_dir().join("manga_downloader_missing_page_test.png");
+
+        create_missing_page(&path, 7, 400, 600).unwrap();
+
+        let page = image::open(&path).unwrap().to_luma8();
+        assert_eq!(page.dimensions(), (400, 600));
+        // Some pixels must have been darkened by the text
+        assert!(page.pixels().any(|p| p.0[0] < BACKGROUND / 2));
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,483 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError, ImageFormat};
+use log::{debug, trace, warn};
+use rayon::prelude::*;
+
+use crate::downloader::image_dimensions;
+use crate::error::DownloadError;
+
+/// Channel difference still considered part of a plain border when auto-cropping
+const BORDER_TOLERANCE: u8 = 24;
+
+/// Default width to height ratio above which a page is treated as a double-page spread
+pub const DEFAULT_SPREAD_RATIO: f32 = 1.2;
+
+/// Format processed pages are saved in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputImageFormat {
+    /// Same format as the downloaded image
+    #[default]
+    Keep,
+    /// JPEG at the configured quality
+    Jpeg,
+    /// Lossy WebP at the configured quality
+    Webp,
+}
+
+/// What to do with double-page spreads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SpreadMode {
+    /// Leave spreads as one landscape page
+    #[default]
+    Keep,
+    /// Cut spreads into two pages, in reading order
+    Split,
+    /// Turn spreads a quarter so they fill a portrait screen
+    Rotate,
+}
+
+/// Changes applied to every page between downloading and exporting
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessingOptions {
+    /// Convert pages to grayscale, e.g. for e-ink readers
+    pub grayscale: bool,
+    /// Downscale pages whose width or height is larger than this many pixels
+    pub max_dimension: Option<u32>,
+    /// Format processed pages are saved in
+    pub format: OutputImageFormat,
+    /// JPEG and WebP quality from 1 to 100
+    pub quality: u8,
+    /// Remove plain white or black borders around the page
+    pub auto_crop: bool,
+    /// Contrast change, negative values reduce it and positive values increase it
+    pub contrast: f32,
+    /// Gamma correction, values above 1 darken mid-tones and values below 1 lighten them
+    pub gamma: f32,
+    /// What to do with double-page spreads
+    pub spreads: SpreadMode,
+    /// Width to height ratio above which a page is treated as a spread
+    pub spread_ratio: f32,
+    /// Pages are read right to left, which decides the order of split halves and the rotation direction
+    pub right_to_left: bool,
+}
+
+impl Default for ProcessingOptions {
+    fn default() -> Self {
+        Self {
+            grayscale: false,
+            max_dimension: None,
+            format: OutputImageFormat::Keep,
+            quality: 85,
+            auto_crop: false,
+            contrast: 0.0,
+            gamma: 1.0,
+            spreads: SpreadMode::Keep,
+            spread_ratio: DEFAULT_SPREAD_RATIO,
+            right_to_left: true,
+        }
+    }
+}
+
+impl ProcessingOptions {
+    /// Whether these options leave pages untouched, so processing can be skipped entirely
+    pub fn is_noop(&self) -> bool {
+        !self.grayscale
+            && self.max_dimension.is_none()
+            && self.format == OutputImageFormat::Keep
+            && !self.auto_crop
+            && self.contrast == 0.0
+            && self.gamma == 1.0
+            && self.spreads == SpreadMode::Keep
+    }
+}
+
+/// Pages of a chapter after processing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessedChapter {
+    /// Images to export, in reading order; a split spread contributes two of them
+    pub image_paths: Vec<PathBuf>,
+    /// Positions (starting at 0) of the source pages that were double-page spreads
+    pub spreads: Vec<usize>,
+}
+
+/// Whether a page of this size is a double-page spread
+pub fn is_spread(width: u32, height: u32, ratio: f32) -> bool {
+    height > 0 && width as f32 > height as f32 * ratio
+}
+
+/// Processes the pages of a chapter on the rayon thread pool, writing the results to `output_dir`
+///
+/// The work runs on a blocking task so it doesn't stall the tokio runtime. Pages that fail
+/// to process are kept as they were, with a warning; the returned paths stay in page order.
+/// Spreads are detected even when nothing else is done to the pages.
+pub async fn process_images(image_paths: Vec<PathBuf>, output_dir: &Path, options: &ProcessingOptions) -> Result<ProcessedChapter, DownloadError> {
+    if options.is_noop() {
+        // Only the image headers are read to find the spreads
+        let spreads = image_paths.iter().enumerate()
+            .filter(|(_, path)| image_dimensions(path).is_some_and(|(width, height)| is_spread(width, height, options.spread_ratio)))
+            .map(|(i, _)| i)
+            .collect();
+        return Ok(ProcessedChapter { image_paths, spreads });
+    }
+
+    debug!("Processing {} images with {:?}", image_paths.len(), options);
+    std::fs::create_dir_all(output_dir)?;
+
+    let output_dir = output_dir.to_path_buf();
+    let options = options.clone();
+    let pages = tokio::task::spawn_blocking(move || {
+        image_paths.into_par_iter()
+            .map(|path| match process_image(&path, &output_dir, &options) {
+                Ok(processed) => processed,
+                Err(e) => {
+                    warn!("Failed to process {:?}, keeping it unchanged: {}", path, e);
+                    let spread = image_dimensions(&path).is_some_and(|(width, height)| is_spread(width, height, options.spread_ratio));
+                    ProcessedPage { image_paths: vec![path], spread }
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| DownloadError::ImageProcessingError(format!("Image processing task failed: {}", e)))?;
+
+    let mut chapter = ProcessedChapter::default();
+    for (i, page) in pages.into_iter().enumerate() {
+        if page.spread {
+            chapter.spreads.push(i);
+        }
+        chapter.image_paths.extend(page.image_paths);
+    }
+    Ok(chapter)
+}
+
+/// One source page after processing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessedPage {
+    /// The processed images, two when a spread was split
+    pub image_paths: Vec<PathBuf>,
+    /// Whether the source page was a double-page spread
+    pub spread: bool,
+}
+
+/// Processes a single page and saves it in `output_dir` under the same name
+///
+/// The extension of the new files follows the format they are saved in. Halves of a
+/// split spread get `_1` and `_2` suffixes, in reading order.
+pub fn process_image(path: &Path, output_dir: &Path, options: &ProcessingOptions) -> Result<ProcessedPage, DownloadError> {
+    let reader = image::ImageReader::open(path)?
+        .with_guessed_format()?;
+    let source_format = reader.format();
+    let mut image = reader.decode()
+        .map_err(|e| DownloadError::ImageProcessingError(format!("Failed to decode {:?}: {}", path, e)))?;
+
+    if options.auto_crop {
+        image = auto_crop(image);
+    }
+    if options.grayscale {
+        image = image.grayscale();
+    }
+    if let Some(max_dimension) = options.max_dimension
+        && (image.width() > max_dimension || image.height() > max_dimension)
+    {
+        // `resize` keeps the aspect ratio and fits the image inside the given bounds
+        image = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+    }
+    if options.contrast != 0.0 {
+        image = image.adjust_contrast(options.contrast);
+    }
+    if options.gamma != 1.0 {
+        image = adjust_gamma(image, options.gamma);
+    }
+
+    let spread = is_spread(image.width(), image.height(), options.spread_ratio);
+    let images = match options.spreads {
+        SpreadMode::Split if spread => split_spread(&image, options.right_to_left).to_vec(),
+        // The page read first ends up on top
+        SpreadMode::Rotate if spread && options.right_to_left => vec![image.rotate270()],
+        SpreadMode::Rotate if spread => vec![image.rotate90()],
+        _ => vec![image],
+    };
+
+    let format = match options.format {
+        OutputImageFormat::Keep => source_format.unwrap_or(ImageFormat::Png),
+        OutputImageFormat::Jpeg => ImageFormat::Jpeg,
+        OutputImageFormat::Webp => ImageFormat::WebP,
+    };
+    let file_stem = path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| DownloadError::ImageProcessingError(format!("Invalid image path: {:?}", path)))?;
+    let extension = format.extensions_str().first().copied().unwrap_or("img");
+
+    let mut image_paths = Vec::new();
+    for (i, image) in images.iter().enumerate() {
+        let file_name = if images.len() == 1 {
+            format!("{}.{}", file_stem, extension)
+        } else {
+            format!("{}_{}.{}", file_stem, i + 1, extension)
+        };
+        let output_path = output_dir.join(file_name);
+        trace!("Saving processed {:?} as {:?}", path, output_path);
+
+        save_image(image, &output_path, format, options.quality)?;
+        image_paths.push(output_path);
+    }
+
+    Ok(ProcessedPage { image_paths, spread })
+}
+
+/// Cut a spread down the middle, returning the halves in reading order
+fn split_spread(image: &DynamicImage, right_to_left: bool) -> [DynamicImage; 2] {
+    let half = image.width() / 2;
+    let left = image.crop_imm(0, 0, half, image.height());
+    let right = image.crop_imm(half, 0, image.width() - half, image.height());
+
+    if right_to_left {
+        [right, left]
+    } else {
+        [left, right]
+    }
+}
+
+/// Encode `image` in `format`, using `quality` for JPEG and WebP
+fn save_image(image: &DynamicImage, path: &Path, format: ImageFormat, quality: u8) -> Result<(), DownloadError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    // JPEG has no alpha channel
+    let result = match format {
+        ImageFormat::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut writer, quality.clamp(1, 100));
+            if image.color().has_color() {
+                image.to_rgb8().write_with_encoder(encoder)
+            } else {
+                image.to_luma8().write_with_encoder(encoder)
+            }
+        }
+        ImageFormat::WebP => {
+            let webp = encode_webp(image, quality)?;
+            writer.write_all(&webp).map_err(ImageError::IoError)
+        }
+        format => image.write_to(&mut writer, format),
+    };
+
+    result.map_err(|e| DownloadError::ImageProcessingError(format!("Failed to save {:?}: {}", path, e)))
+}
+
+/// Encode `image` as lossy WebP at `quality`
+///
+/// libwebp only takes RGB and RGBA pixels, so grayscale pages are expanded first.
+fn encode_webp(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, DownloadError> {
+    let quality = f32::from(quality.clamp(1, 100));
+    let encoded = if image.color().has_alpha() {
+        let rgba = image.to_rgba8();
+        webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height()).encode_simple(false, quality)
+    } else {
+        let rgb = image.to_rgb8();
+        webp::Encoder::from_rgb(&rgb, rgb.width(), rgb.height()).encode_simple(false, quality)
+    };
+    encoded
+        .map(|memory| memory.to_vec())
+        .map_err(|e| DownloadError::ImageProcessingError(format!("Failed to encode WebP: {:?}", e)))
+}
+
+/// Remove plain white or black borders, judged from the color of the top-left corner
+///
+/// Images whose corner is neither close to white nor to black, or that are blank
+/// all over, are returned unchanged.
+pub fn auto_crop(image: DynamicImage) -> DynamicImage {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    if width == 0 || height == 0 {
+        return image;
+    }
+
+    let background = match luma.get_pixel(0, 0).0[0] {
+        value if value >= 255 - BORDER_TOLERANCE => 255,
+        value if value <= BORDER_TOLERANCE => 0,
+        _ => return image,
+    };
+    let is_border = |x: u32, y: u32| luma.get_pixel(x, y).0[0].abs_diff(background) <= BORDER_TOLERANCE;
+    let row_is_border = |y: u32| (0..width).all(|x| is_border(x, y));
+    let column_is_border = |x: u32, top: u32, bottom: u32| (top..bottom).all(|y| is_border(x, y));
+
+    let Some(top) = (0..height).find(|&y| !row_is_border(y)) else {
+        return image;
+    };
+    let bottom = (top..height).rev().find(|&y| !row_is_border(y)).map_or(height, |y| y + 1);
+    let left = (0..width).find(|&x| !column_is_border(x, top, bottom)).unwrap_or(0);
+    let right = (left..width).rev().find(|&x| !column_is_border(x, top, bottom)).map_or(width, |x| x + 1);
+
+    if (left, top, right, bottom) == (0, 0, width, height) {
+        return image;
+    }
+    trace!("Auto-cropping {}x{} image to {}x{} at ({}, {})", width, height, right - left, bottom - top, left, top);
+    image.crop_imm(left, top, right - left, bottom - top)
+}
+
+/// Apply gamma correction to the color channels, leaving alpha untouched
+fn adjust_gamma(image: DynamicImage, gamma: f32) -> DynamicImage {
+    let lut: Vec<u8> = (0..=255u8)
+        .map(|value| ((f32::from(value) / 255.0).powf(gamma) * 255.0).round() as u8)
+        .collect();
+
+    match image {
+        DynamicImage::ImageLuma8(mut buffer) => {
+            buffer.pixels_mut().for_each(|pixel| pixel.0[0] = lut[pixel.0[0] as usize]);
+            DynamicImage::ImageLuma8(buffer)
+        }
+        DynamicImage::ImageLumaA8(mut buffer) => {
+            buffer.pixels_mut().for_each(|pixel| pixel.0[0] = lut[pixel.0[0] as usize]);
+            DynamicImage::ImageLumaA8(buffer)
+        }
+        DynamicImage::ImageRgb8(mut buffer) => {
+            buffer.pixels_mut().flat_map(|pixel| pixel.0.iter_mut()).for_each(|value| *value = lut[*value as usize]);
+            DynamicImage::ImageRgb8(buffer)
+        }
+        image => {
+            let mut buffer = image.to_rgba8();
+            buffer.pixels_mut().flat_map(|pixel| pixel.0[..3].iter_mut()).for_each(|value| *value = lut[*value as usize]);
+            DynamicImage::ImageRgba8(buffer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, GrayImage, Luma, RgbImage, Rgb};
+
+    /// A white page with a dark rectangle covering `(x, y, width, height)`
+    fn framed_page(width: u32, height: u32, content: (u32, u32, u32, u32)) -> DynamicImage {
+        let (x, y, w, h) = content;
+        let page = GrayImage::from_fn(width, height, |px, py| {
+            if px >= x && px < x + w && py >= y && py < y + h { Luma([30]) } else { Luma([250]) }
+        });
+        DynamicImage::ImageLuma8(page)
+    }
+
+    #[test]
+    fn test_auto_crop_removes_white_borders() {
+        let cropped = auto_crop(framed_page(100, 150, (10, 20, 70, 100)));
+        assert_eq!(cropped.dimensions(), (70, 100));
+
+        // Nothing to crop on a blank page
+        let blank = auto_crop(framed_page(50, 50, (0, 0, 0, 0)));
+        assert_eq!(blank.dimensions(), (50, 50));
+    }
+
+    #[test]
+    fn test_adjust_gamma() {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(2, 2, Luma([128])));
+
+        let darker = adjust_gamma(image.clone(), 2.0).to_luma8();
+        let lighter = adjust_gamma(image, 0.5).to_luma8();
+        assert!(darker.get_pixel(0, 0).0[0] < 128);
+        assert!(lighter.get_pixel(0, 0).0[0] > 128);
+    }
+
+    #[test]
+    fn test_process_image_downscales_and_re_encodes() {
+        let dir = std::env::temp_dir().join("manga_downloader_processing_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("image_0000.png");
+        RgbImage::from_pixel(400, 1000, Rgb([200, 40, 40])).save(&source).unwrap();
+
+        let options = ProcessingOptions {
+            grayscale: true,
+            max_dimension: Some(500),
+            format: OutputImageFormat::Jpeg,
+            ..ProcessingOptions::default()
+        };
+        std::fs::create_dir_all(dir.join("processed")).unwrap();
+        let output = process_image(&source, &dir.join("processed"), &options).unwrap();
+        assert_eq!(output.image_paths, vec![dir.join("processed").join("image_0000.jpg")]);
+        assert!(!output.spread);
+
+        let processed = image::open(&output.image_paths[0]).unwrap();
+        assert_eq!(processed.dimensions(), (200, 500));
+        assert!(!processed.color().has_color());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_webp_honours_quality() {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(64, 64, |x, y| Luma([((x * 7 + y * 13) % 256) as u8])));
+        let low = encode_webp(&image, 10).unwrap();
+        let high = encode_webp(&image, 100).unwrap();
+        assert!(low.len() < high.len());
+
+        let decoded = image::load_from_memory_with_format(&low, ImageFormat::WebP).unwrap();
+        assert_eq!(decoded.dimensions(), (64, 64));
+    }
+
+    #[tokio::test]
+    async fn test_process_images_keeps_page_order() {
+        let dir = std::env::temp_dir().join("manga_downloader_processing_order_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let pages = (0..6).map(|i| {
+            let path = dir.join(format!("image_{:04}.png", i));
+            GrayImage::from_pixel(10 + i, 10, Luma([i as u8 * 40])).save(&path).unwrap();
+            path
+        }).collect::<Vec<_>>();
+        let missing = dir.join("image_9999.png");
+
+        let mut inputs = pages.clone();
+        inputs.push(missing.clone());
+        let options = ProcessingOptions { format: OutputImageFormat::Webp, ..ProcessingOptions::default() };
+        let processed = process_images(inputs, &dir.join("processed"), &options).await.unwrap();
+        let outputs = processed.image_paths;
+
+        for (i, output) in outputs[..6].iter().enumerate() {
+            assert_eq!(*output, dir.join("processed").join(format!("image_{:04}.webp", i)));
+            assert_eq!(image::open(output).unwrap().width(), 10 + i as u32);
+        }
+        // Pages that can't be processed are kept as they were
+        assert_eq!(outputs[6], missing);
+        // Widths 13 to 15 are wider than 1.2 times the height of 10
+        assert_eq!(processed.spreads, vec![3, 4, 5]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_split_spread_in_reading_order() {
+        let dir = std::env::temp_dir().join("manga_downloader_spread_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("image_0003.png");
+        // Dark left half, light right half
+        GrayImage::from_fn(200, 100, |x, _| if x < 100 { Luma([0]) } else { Luma([255]) }).save(&source).unwrap();
+
+        let options = ProcessingOptions { spreads: SpreadMode::Split, ..ProcessingOptions::default() };
+        let output_dir = dir.join("processed");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let page = process_image(&source, &output_dir, &options).unwrap();
+
+        assert!(page.spread);
+        assert_eq!(page.image_paths, vec![output_dir.join("image_0003_1.png"), output_dir.join("image_0003_2.png")]);
+        // Right to left: the right half is read first
+        let first = image::open(&page.image_paths[0]).unwrap().to_luma8();
+        assert_eq!(first.dimensions(), (100, 100));
+        assert_eq!(first.get_pixel(0, 0).0[0], 255);
+
+        let rotate = ProcessingOptions { spreads: SpreadMode::Rotate, ..ProcessingOptions::default() };
+        let page = process_image(&source, &output_dir, &rotate).unwrap();
+        let rotated = image::open(&page.image_paths[0]).unwrap().to_luma8();
+        assert_eq!(rotated.dimensions(), (100, 200));
+        // Counter-clockwise turn puts the right half on top
+        assert_eq!(rotated.get_pixel(0, 0).0[0], 255);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_is_spread() {
+        assert!(is_spread(1600, 1200, DEFAULT_SPREAD_RATIO));
+        assert!(!is_spread(1100, 1000, DEFAULT_SPREAD_RATIO));
+        assert!(!is_spread(800, 1200, DEFAULT_SPREAD_RATIO));
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::trace;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Politeness limits applied to every host separately
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    /// Sustained requests per second per host; 0 disables the limit
+    pub requests_per_second: f64,
+    /// Requests that may be sent back to back before the rate applies
+    pub burst: u32,
+    /// Requests in flight at the same time per host; 0 disables the limit
+    pub max_in_flight: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 4.0,
+            burst: 1,
+            max_in_flight: 4,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Limits that never delay a request
+    pub fn unlimited() -> Self {
+        Self {
+            requests_per_second: 0.0,
+            burst: 1,
+            max_in_flight: 0,
+        }
+    }
+}
+
+/// Token-bucket rate limiter keyed by host
+///
+/// Clones share the same buckets, so one limiter covers every request of a run no
+/// matter whether it comes from the chapter or the image download stage.
+#[derive(Debug, Clone)]
+pub struct HostRateLimiter {
+    config: RateLimitConfig,
+    hosts: Arc<Mutex<HashMap<String, Arc<HostLimit>>>>,
+}
+
+/// Permission to send one request; the in-flight slot is released when dropped
+#[derive(Debug)]
+pub struct RatePermit {
+    _in_flight: Option<OwnedSemaphorePermit>,
+}
+
+#[derive(Debug)]
+struct HostLimit {
+    bucket: Mutex<TokenBucket>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// A token taken for a request that is still waiting for it; dropping it returns the token
+struct Reservation<'a> {
+    limit: &'a HostLimit,
+    capacity: f64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut bucket = self.limit.bucket.lock().unwrap();
+        bucket.tokens = (bucket.tokens + 1.0).min(self.capacity);
+    }
+}
+
+impl HostRateLimiter {
+    /// Create a limiter applying `config` to every host
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Wait until a request to the host of `url` may be sent
+    pub async fn acquire(&self, url: &str) -> RatePermit {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+            .unwrap_or_default();
+        let limit = self.host_limit(&host);
+
+        // Take the in-flight slot first so queued requests don't burn tokens while waiting
+        let in_flight = match &limit.in_flight {
+            Some(semaphore) => Arc::clone(semaphore).acquire_owned().await.ok(),
+            None => None,
+        };
+
+        let wait = self.reserve_token(&limit);
+        if !wait.is_zero() {
+            // Give the token back if the caller stops waiting, so later requests aren't pushed back
+            let reservation = Reservation { limit: &limit, capacity: f64::from(self.config.burst.max(1)) };
+            trace!("Rate limiting {}: waiting {:.2}s", host, wait.as_secs_f64());
+            tokio::time::sleep(wait).await;
+            std::mem::forget(reservation);
+        }
+
+        RatePermit { _in_flight: in_flight }
+    }
+
+    fn host_limit(&self, host: &str) -> Arc<HostLimit> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let limit = hosts.entry(host.to_string()).or_insert_with(|| {
+            Arc::new(HostLimit {
+                bucket: Mutex::new(TokenBucket {
+                    tokens: f64::from(self.config.burst.max(1)),
+                    last_refill: Instant::now(),
+                }),
+                in_flight: (self.config.max_in_flight > 0)
+                    .then(|| Arc::new(Semaphore::new(self.config.max_in_flight))),
+            })
+        });
+        Arc::clone(limit)
+    }
+
+    /// Take a token from the bucket and return how long to wait before it is actually available
+    ///
+    /// Tokens may go negative: every caller reserves its own slot, so waiting requests are
+    /// spaced out evenly instead of all waking up at once.
+    fn reserve_token(&self, limit: &HostLimit) -> Duration {
+        let rate = self.config.requests_per_second;
+        if rate <= 0.0 {
+            return Duration::ZERO;
+        }
+
+        let capacity = f64::from(self.config.burst.max(1));
+        let mut bucket = limit.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.last_refill = now;
+        bucket.tokens -= 1.0;
+
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_requests_are_spaced_per_host() {
+        let limiter = HostRateLimiter::new(RateLimitConfig {
+            requests_per_second: 20.0,
+            burst: 1,
+            max_in_flight: 0,
+        });
+
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire("https://example.com/image.jpg").await;
+        }
+        // The first request is free, the other four wait 50ms each
+        assert!(start.elapsed() >= Duration::from_millis(190));
+
+        // Another host has its own bucket
+        let start = Instant::now();
+        limiter.acquire("https://cdn.example.org/image.jpg").await;
+        assert!(start.elapsed() < Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_requests_are_capped() {
+        let limiter = HostRateLimiter::new(RateLimitConfig {
+            requests_per_second: 0.0,
+            burst: 1,
+            max_in_flight: 1,
+        });
+
+        let permit = limiter.acquire("https://example.com/1.jpg").await;
+        let blocked = tokio::time::timeout(Duration::from_millis(50), limiter.acquire("https://example.com/2.jpg")).await;
+        assert!(blocked.is_err());
+
+        drop(permit);
+        let unblocked = tokio::time::timeout(Duration::from_millis(50), limiter.acquire("https://example.com/2.jpg")).await;
+        assert!(unblocked.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_requests_give_their_token_back() {
+        let limiter = HostRateLimiter::new(RateLimitConfig {
+            requests_per_second: 10.0,
+            burst: 1,
+            max_in_flight: 0,
+        });
+
+        let start = Instant::now();
+        limiter.acquire("https://example.com/1.jpg").await;
+        let abandoned = tokio::time::timeout(Duration::from_millis(20), limiter.acquire("https://example.com/2.jpg")).await;
+        assert!(abandoned.is_err());
+
+        // The next request takes the abandoned slot, 100ms after the first, not 200ms
+        limiter.acquire("https://example.com/3.jpg").await;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+        assert!(start.elapsed() < Duration::from_millis(170));
+    }
+}
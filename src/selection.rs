@@ -0,0 +1,179 @@
+use std::str::FromStr;
+
+use log::{debug, warn};
+
+use crate::error::DownloadError;
+use crate::manga_to_download::ChapterInfo;
+
+/// One term of a chapter selection expression
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    /// Every chapter
+    All,
+    /// The chapter with this number, e.g. `45.5`
+    Number(f64),
+    /// Chapters numbered from the first to the second number, both included
+    Range(f64, f64),
+    /// The newest chapters
+    Latest(usize),
+    /// The chapter with this number and every chapter after it
+    Since(f64),
+}
+
+/// A non-interactive chapter selection such as `12-20,45.5,!15`, `latest:5` or `since:100`
+///
+/// Terms are separated by commas. Numbers are chapter numbers taken from the chapter titles,
+/// not positions in the list, so an expression keeps selecting the same chapters when new
+/// ones are posted. Terms starting with `!` remove chapters from the selection; an
+/// expression made only of exclusions starts from every chapter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterSelector {
+    include: Vec<Term>,
+    exclude: Vec<Term>,
+}
+
+impl FromStr for ChapterSelector {
+    type Err = DownloadError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut selector = ChapterSelector { include: Vec::new(), exclude: Vec::new() };
+
+        for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.strip_prefix('!') {
+                Some(excluded) => selector.exclude.push(parse_term(excluded.trim())?),
+                None => selector.include.push(parse_term(part)?),
+            }
+        }
+
+        if selector.include.is_empty() && selector.exclude.is_empty() {
+            return Err(DownloadError::ParsingError(String::from("Empty chapter selection")));
+        }
+        if selector.include.is_empty() {
+            selector.include.push(Term::All);
+        }
+        Ok(selector)
+    }
+}
+
+impl ChapterSelector {
+    /// Positions in `chapters` (oldest first) of the selected chapters, in reading order
+    pub fn resolve(&self, chapters: &[ChapterInfo]) -> Result<Vec<usize>, DownloadError> {
+        let numbers = chapters.iter().map(|chapter| chapter.number.chapter).collect::<Vec<_>>();
+
+        let mut selected = vec![false; chapters.len()];
+        for term in &self.include {
+            let matches = term_matches(term, &numbers);
+            if !matches.iter().any(|&m| m) {
+                warn!("No chapter matches '{}'", describe(term));
+            }
+            selected.iter_mut().zip(matches).for_each(|(selected, m)| *selected |= m);
+        }
+        for term in &self.exclude {
+            let matches = term_matches(term, &numbers);
+            selected.iter_mut().zip(matches).for_each(|(selected, m)| *selected &= !m);
+        }
+
+        let selected = selected.iter().enumerate()
+            .filter(|(_, selected)| **selected)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        debug!("Chapter selection resolved to positions {:?}", selected);
+
+        if selected.is_empty() {
+            return Err(DownloadError::ParsingError(String::from("No chapters match the selection")));
+        }
+        Ok(selected)
+    }
+}
+
+fn parse_term(part: &str) -> Result<Term, DownloadError> {
+    let invalid = || DownloadError::ParsingError(format!("Invalid chapter selection '{}'", part));
+    let number = |text: &str| text.trim().parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0).ok_or_else(invalid);
+
+    let part = part.to_lowercase();
+    if part == "all" {
+        Ok(Term::All)
+    } else if let Some(count) = part.strip_prefix("latest:") {
+        count.trim().parse::<usize>().ok().filter(|&count| count > 0).map(Term::Latest).ok_or_else(invalid)
+    } else if let Some(since) = part.strip_prefix("since:") {
+        Ok(Term::Since(number(since)?))
+    } else if let Some((start, end)) = part.split_once('-') {
+        let (start, end) = (number(start)?, number(end)?);
+        if start > end {
+            return Err(invalid());
+        }
+        Ok(Term::Range(start, end))
+    } else {
+        Ok(Term::Number(number(&part)?))
+    }
+}
+
+/// Which chapters, given their numbers in reading order, a term selects
+fn term_matches(term: &Term, numbers: &[Option<f64>]) -> Vec<bool> {
+    match *term {
+        Term::All => vec![true; numbers.len()],
+        Term::Number(n) => numbers.iter().map(|number| *number == Some(n)).collect(),
+        Term::Range(start, end) => numbers.iter()
+            .map(|number| number.is_some_and(|number| number >= start && number <= end))
+            .collect(),
+        Term::Latest(count) => (0..numbers.len()).map(|i| i + count >= numbers.len()).collect(),
+        Term::Since(n) => {
+            // Unnumbered chapters after the starting chapter, such as extras, are included
+            let start = numbers.iter().position(|number| number.is_some_and(|number| number >= n));
+            (0..numbers.len()).map(|i| start.is_some_and(|start| i >= start)).collect()
+        }
+    }
+}
+
+fn describe(term: &Term) -> String {
+    match term {
+        Term::All => String::from("all"),
+        Term::Number(n) => n.to_string(),
+        Term::Range(start, end) => format!("{}-{}", start, end),
+        Term::Latest(count) => format!("latest:{}", count),
+        Term::Since(n) => format!("since:{}", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapters(titles: &[&str]) -> Vec<ChapterInfo> {
+        titles.iter().enumerate()
+            .map(|(index, title)| ChapterInfo::new(index, title.to_string(), format!("https://example.com/manga/example/{}/", index)))
+            .collect()
+    }
+
+    fn select(expression: &str, chapters: &[ChapterInfo]) -> Vec<usize> {
+        expression.parse::<ChapterSelector>().unwrap().resolve(chapters).unwrap()
+    }
+
+    #[test]
+    fn test_selection_uses_chapter_numbers() {
+        let chapters = chapters(&["Chapter 1", "Chapter 2", "Chapter 2.5", "Extra", "Chapter 3", "Chapter 10"]);
+
+        assert_eq!(select("2-3", &chapters), vec![1, 2, 4]);
+        assert_eq!(select("2.5,10", &chapters), vec![2, 5]);
+        assert_eq!(select("latest:2", &chapters), vec![4, 5]);
+        assert_eq!(select("since:2.5", &chapters), vec![2, 3, 4, 5]);
+        assert_eq!(select("all,!2-3", &chapters), vec![0, 3, 5]);
+        assert_eq!(select("!1, !latest:1", &chapters), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_selection_falls_back_to_url() {
+        let chapters = vec![ChapterInfo::new(0, String::from("The Beginning"), String::from("https://example.com/manga/example/chapter-7/"))];
+        assert_eq!(select("7", &chapters), vec![0]);
+    }
+
+    #[test]
+    fn test_invalid_selections() {
+        for expression in ["", "abc", "5-2", "latest:0", "since:", "1-2-3"] {
+            assert!(expression.parse::<ChapterSelector>().is_err(), "{}", expression);
+        }
+        // A selection matching no chapter at all is an error
+        let selector = "40".parse::<ChapterSelector>().unwrap();
+        assert!(selector.resolve(&chapters(&["Chapter 1"])).is_err());
+    }
+}
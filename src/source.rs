@@ -0,0 +1,562 @@
+use std::sync::Arc;
+
+use log::{debug, warn};
+
+use crate::error::DownloadError;
+use crate::manga_to_download::ChapterInfo;
+use crate::metadata::{SeriesMetadata, SeriesStatus};
+use crate::source_profile::SourceProfile;
+
+/// A manga website that series, chapters and page images can be scraped from
+pub trait Source: Send + Sync {
+    /// Human readable name of the source, used in logs
+    fn name(&self) -> &str;
+
+    /// Whether this source knows how to scrape pages served from `host`
+    fn handles_host(&self, host: &str) -> bool;
+
+    /// Extract the series title from a series page
+    fn series_title(&self, document: &scraper::Html) -> Result<String, DownloadError>;
+
+    /// Extract the author names from a series page, if the site lists them
+    fn series_authors(&self, _document: &scraper::Html) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Extract the genres from a series page, if the site lists them
+    fn series_genres(&self, _document: &scraper::Html) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Extract the rest of what a series page says about the series, such as the artists,
+    /// status, description and cover
+    ///
+    /// The title, URL, authors and genres are filled in by the caller.
+    fn series_metadata(&self, _document: &scraper::Html) -> SeriesMetadata {
+        SeriesMetadata::default()
+    }
+
+    /// List the chapters linked from a series page, in page order
+    ///
+    /// The returned `ChapterInfo::index` values are ignored; numbering is done by the caller.
+    fn chapters(&self, document: &scraper::Html) -> Result<Vec<ChapterInfo>, DownloadError>;
+
+    /// Extract the chapter title from a chapter page
+    fn chapter_title(&self, document: &scraper::Html) -> Result<String, DownloadError>;
+
+    /// List the page image URLs of a chapter page, in reading order
+    fn page_images(&self, document: &scraper::Html) -> Result<Vec<String>, DownloadError>;
+
+    /// Requests to try, in order, when the series page doesn't embed the chapter list
+    ///
+    /// Their responses are parsed with [`Source::chapters`].
+    fn chapter_list_requests(&self, _series_url: &str, _document: &scraper::Html) -> Vec<ChapterListRequest> {
+        Vec::new()
+    }
+}
+
+/// A POST request that returns an HTML fragment containing the chapter list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterListRequest {
+    /// URL to post to
+    pub url: String,
+    /// Form fields sent as the request body
+    pub form: Vec<(String, String)>,
+}
+
+/// Source for sites built on the WordPress Madara theme, such as mangaread.org
+///
+/// The selectors used for scraping come from a [`SourceProfile`].
+#[derive(Debug, Clone)]
+pub struct MadaraSource {
+    profile: SourceProfile,
+    title_selector: scraper::Selector,
+    author_selector: scraper::Selector,
+    genre_selector: scraper::Selector,
+    artist_selector: scraper::Selector,
+    tag_selector: scraper::Selector,
+    description_selector: scraper::Selector,
+    cover_selector: scraper::Selector,
+    summary_item_selector: scraper::Selector,
+    summary_heading_selector: scraper::Selector,
+    summary_content_selector: scraper::Selector,
+    chapter_list_selector: scraper::Selector,
+    chapter_title_selector: scraper::Selector,
+    image_selector: scraper::Selector,
+}
+
+impl MadaraSource {
+    /// Create a Madara source scraping with the selectors of `profile`
+    pub fn from_profile(profile: SourceProfile) -> Result<Self, DownloadError> {
+        Ok(Self {
+            title_selector: parse_selector(&profile.title_selector)?,
+            author_selector: parse_selector(&profile.author_selector)?,
+            genre_selector: parse_selector(&profile.genre_selector)?,
+            artist_selector: parse_selector(&profile.artist_selector)?,
+            tag_selector: parse_selector(&profile.tag_selector)?,
+            description_selector: parse_selector(&profile.description_selector)?,
+            cover_selector: parse_selector(&profile.cover_selector)?,
+            summary_item_selector: parse_selector(&profile.summary_item_selector)?,
+            summary_heading_selector: parse_selector(&profile.summary_heading_selector)?,
+            summary_content_selector: parse_selector(&profile.summary_content_selector)?,
+            chapter_list_selector: parse_selector(&profile.chapter_list_selector)?,
+            chapter_title_selector: parse_selector(&profile.chapter_title_selector)?,
+            image_selector: parse_selector(&profile.image_selector)?,
+            profile,
+        })
+    }
+
+    /// The built-in source for https://www.mangaread.org
+    pub fn mangaread() -> Self {
+        Self::from_profile(SourceProfile::mangaread())
+            .expect("built-in source profile has valid selectors")
+    }
+
+    /// The profile this source scrapes with
+    pub fn profile(&self) -> &SourceProfile {
+        &self.profile
+    }
+
+    /// Heading and content text of the items in the summary table of a series page
+    fn summary_items(&self, document: &scraper::Html) -> Vec<(String, String)> {
+        document.select(&self.summary_item_selector)
+            .filter_map(|item| Some((
+                collapsed_text(item.select(&self.summary_heading_selector).next()?),
+                collapsed_text(item.select(&self.summary_content_selector).next()?),
+            )))
+            .collect()
+    }
+}
+
+impl Source for MadaraSource {
+    fn name(&self) -> &str {
+        &self.profile.name
+    }
+
+    fn handles_host(&self, host: &str) -> bool {
+        self.profile.hosts.is_empty() || self.profile.hosts.iter().any(|h| host_matches(host, h))
+    }
+
+    fn series_title(&self, document: &scraper::Html) -> Result<String, DownloadError> {
+        let title = document.select(&self.title_selector).next()
+            .ok_or_else(|| DownloadError::ElementNotFound(String::from("Manga title element not found")))?;
+
+        Ok(title.text().collect::<Vec<_>>().join(" "))
+    }
+
+    fn series_authors(&self, document: &scraper::Html) -> Vec<String> {
+        select_texts(document, &self.author_selector)
+    }
+
+    fn series_genres(&self, document: &scraper::Html) -> Vec<String> {
+        select_texts(document, &self.genre_selector)
+    }
+
+    fn series_metadata(&self, document: &scraper::Html) -> SeriesMetadata {
+        let mut metadata = SeriesMetadata {
+            artists: select_texts(document, &self.artist_selector),
+            tags: select_texts(document, &self.tag_selector),
+            description: document.select(&self.description_selector).next()
+                .map(collapsed_text)
+                .filter(|text| !text.is_empty()),
+            // Covers are lazy-loaded like page images on many Madara sites
+            cover_url: document.select(&self.cover_selector).next()
+                .and_then(|e| first_attribute(e, &self.profile.cover_attributes)),
+            ..SeriesMetadata::default()
+        };
+
+        // The rest is in the summary table, as heading and content pairs
+        for (heading, content) in self.summary_items(document) {
+            let heading = heading.to_lowercase();
+            if heading.starts_with("alternative") {
+                metadata.alternative_titles = content.split([',', ';'])
+                    .map(|title| title.trim().to_string())
+                    .filter(|title| !title.is_empty())
+                    .collect();
+            } else if heading.starts_with("status") {
+                metadata.status = SeriesStatus::parse(&content);
+            } else if heading.starts_with("release") {
+                metadata.release_year = content.split(|c: char| !c.is_ascii_digit())
+                    .find(|part| part.len() == 4)
+                    .and_then(|year| year.parse().ok());
+            } else if heading.starts_with("rating") {
+                metadata.rating = content.split(|c: char| !c.is_ascii_digit() && c != '.')
+                    .find_map(|part| part.parse().ok());
+            }
+        }
+
+        metadata
+    }
+
+    fn chapters(&self, document: &scraper::Html) -> Result<Vec<ChapterInfo>, DownloadError> {
+        let chapters = document.select(&self.chapter_list_selector)
+            .filter_map(|e| {
+                let url = e.attr("href")?.to_string();
+                let title = e.text().collect::<Vec<_>>().join(" ").trim().to_string();
+                Some(ChapterInfo::new(0, title, url))
+            })
+            .collect();
+
+        Ok(chapters)
+    }
+
+    fn chapter_title(&self, document: &scraper::Html) -> Result<String, DownloadError> {
+        let title = document.select(&self.chapter_title_selector).next()
+            .ok_or_else(|| DownloadError::ElementNotFound(String::from("Chapter heading element not found")))?;
+
+        Ok(title.text().collect::<Vec<_>>().join(" "))
+    }
+
+    fn page_images(&self, document: &scraper::Html) -> Result<Vec<String>, DownloadError> {
+        // Lazy-loading sites keep the real URL in attributes like data-src, so take
+        // the first attribute from the profile that holds a non-empty value
+        let images = document.select(&self.image_selector)
+            .filter_map(|e| first_attribute(e, &self.profile.image_attributes))
+            .collect();
+
+        Ok(images)
+    }
+
+    fn chapter_list_requests(&self, series_url: &str, document: &scraper::Html) -> Vec<ChapterListRequest> {
+        let mut requests = Vec::new();
+
+        // Newer Madara versions serve the list from <series-url>/ajax/chapters/
+        requests.push(ChapterListRequest {
+            url: format!("{}/ajax/chapters/", series_url.trim_end_matches('/')),
+            form: Vec::new(),
+        });
+
+        // Older ones go through admin-ajax.php, which needs the WordPress post id
+        let ajax_url = reqwest::Url::parse(series_url)
+            .and_then(|url| url.join("/wp-admin/admin-ajax.php"));
+        match (ajax_url, madara_post_id(document)) {
+            (Ok(ajax_url), Some(post_id)) => requests.push(ChapterListRequest {
+                url: ajax_url.to_string(),
+                form: vec![
+                    (String::from("action"), String::from("manga_get_chapters")),
+                    (String::from("manga"), post_id),
+                ],
+            }),
+            _ => debug!("No Madara post id found, skipping admin-ajax.php fallback"),
+        }
+
+        requests
+    }
+}
+
+/// Find the WordPress post id of a Madara series page
+fn madara_post_id(document: &scraper::Html) -> Option<String> {
+    let candidates = [
+        ("#manga-chapters-holder", "data-id"),
+        (".rating-post-id", "value"),
+        (".wp-manga-action-button", "data-post"),
+    ];
+
+    candidates.iter().find_map(|(selector, attr)| {
+        let selector = scraper::Selector::parse(selector).ok()?;
+        document.select(&selector)
+            .filter_map(|e| e.attr(attr))
+            .map(|id| id.trim())
+            .find(|id| !id.is_empty())
+            .map(String::from)
+    })
+}
+
+/// Picks the source responsible for a manga link based on its host
+pub struct SourceRegistry {
+    sources: Vec<Arc<dyn Source>>,
+    fallback: Option<Arc<dyn Source>>,
+}
+
+impl SourceRegistry {
+    /// Create an empty registry without any sources
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    /// Register a source; sources registered first take precedence
+    pub fn register(&mut self, source: Arc<dyn Source>) {
+        self.sources.push(source);
+    }
+
+    /// Register a source that takes precedence over all previously registered ones
+    pub fn register_preferred(&mut self, source: Arc<dyn Source>) {
+        self.sources.insert(0, source);
+    }
+
+    /// Set the source used for hosts that no registered source handles
+    pub fn set_fallback(&mut self, source: Arc<dyn Source>) {
+        self.fallback = Some(source);
+    }
+
+    /// Find the source for the host of `link`
+    pub fn source_for(&self, link: &str) -> Result<Arc<dyn Source>, DownloadError> {
+        let url = reqwest::Url::parse(link)
+            .map_err(|e| DownloadError::ParsingError(format!("Invalid manga link {}: {}", link, e)))?;
+        let host = url.host_str()
+            .ok_or_else(|| DownloadError::ParsingError(format!("Manga link has no host: {}", link)))?;
+
+        if let Some(source) = self.sources.iter().find(|s| s.handles_host(host)) {
+            debug!("Using source {} for host {}", source.name(), host);
+            return Ok(Arc::clone(source));
+        }
+
+        match &self.fallback {
+            Some(source) => {
+                warn!("No source registered for host {}, falling back to {}", host, source.name());
+                Ok(Arc::clone(source))
+            }
+            None => Err(DownloadError::UnsupportedSource(host.to_string())),
+        }
+    }
+}
+
+impl Default for SourceRegistry {
+    /// Registry with the built-in sources, falling back to the generic Madara scraper
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(MadaraSource::mangaread()));
+        registry.set_fallback(Arc::new(MadaraSource::from_profile(SourceProfile {
+            name: String::from("madara"),
+            ..SourceProfile::default()
+        }).expect("built-in source profile has valid selectors")));
+        registry
+    }
+}
+
+/// Whether `host` is `domain` or one of its subdomains
+fn host_matches(host: &str, domain: &str) -> bool {
+    let host = host.to_lowercase();
+    let domain = domain.to_lowercase();
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Trimmed, non-empty text of every element matching `selector`
+fn select_texts(document: &scraper::Html, selector: &scraper::Selector) -> Vec<String> {
+    document.select(selector)
+        .map(|e| e.text().collect::<String>().trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+/// Text of an element with runs of whitespace, including line breaks, collapsed to one space
+fn collapsed_text(element: scraper::ElementRef) -> String {
+    element.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Trimmed value of the first of `attributes` the element has with a non-empty value
+fn first_attribute(element: scraper::ElementRef, attributes: &[String]) -> Option<String> {
+    attributes.iter()
+        .filter_map(|attr| element.attr(attr))
+        .map(|value| value.trim())
+        .find(|value| !value.is_empty())
+        .map(String::from)
+}
+
+fn parse_selector(selector: &str) -> Result<scraper::Selector, DownloadError> {
+    scraper::Selector::parse(selector)
+        .map_err(|_| DownloadError::SelectorError(format!("Failed to parse {} selector", selector)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_picks_source_by_host() {
+        let registry = SourceRegistry::default();
+
+        let source = registry.source_for("https://www.mangaread.org/manga/example/").unwrap();
+        assert_eq!(source.name(), "mangaread.org");
+
+        let source = registry.source_for("https://other-madara-site.com/manga/example/").unwrap();
+        assert_eq!(source.name(), "madara");
+    }
+
+    #[test]
+    fn test_profile_without_hosts_is_preferred_for_any_link() {
+        let mut registry = SourceRegistry::default();
+        let profile = SourceProfile::from_toml(r#"name = "my-site""#).unwrap();
+        registry.register_preferred(Arc::new(MadaraSource::from_profile(profile).unwrap()));
+
+        let source = registry.source_for("https://other-madara-site.com/manga/example/").unwrap();
+        assert_eq!(source.name(), "my-site");
+    }
+
+    #[test]
+    fn test_registry_without_fallback_rejects_unknown_host() {
+        let mut registry = SourceRegistry::new();
+        registry.register(Arc::new(MadaraSource::mangaread()));
+
+        assert!(registry.source_for("https://mangaread.org/manga/example/").is_ok());
+        assert!(matches!(
+            registry.source_for("https://example.com/manga/example/"),
+            Err(DownloadError::UnsupportedSource(_))
+        ));
+    }
+
+    #[test]
+    fn test_madara_source_parses_chapter_page() {
+        let html = r#"
+            <h1 id="chapter-heading">Example - Chapter 1</h1>
+            <div class="page-break"><img src=" https://cdn.example.com/1.jpg "></div>
+            <div class="page-break"><img data-cfsrc="https://cdn.example.com/2.jpg"></div>
+            <div class="page-break"><img></div>
+        "#;
+        let document = scraper::Html::parse_document(html);
+        let source = MadaraSource::mangaread();
+
+        assert_eq!(source.chapter_title(&document).unwrap(), "Example - Chapter 1");
+        assert_eq!(
+            source.page_images(&document).unwrap(),
+            vec!["https://cdn.example.com/1.jpg", "https://cdn.example.com/2.jpg"]
+        );
+    }
+
+    #[test]
+    fn test_madara_source_parses_series_details() {
+        let html = r#"
+            <div class="post-title"><h1>Example Manga</h1></div>
+            <div class="author-content"><a href="/a/1">Jane Doe</a><a href="/a/2"> John Roe </a></div>
+            <div class="genres-content"><a href="/g/1">Action</a>, <a href="/g/2">Comedy</a></div>
+        "#;
+        let document = scraper::Html::parse_document(html);
+        let source = MadaraSource::mangaread();
+
+        assert_eq!(source.series_title(&document).unwrap(), "Example Manga");
+        assert_eq!(source.series_authors(&document), vec!["Jane Doe", "John Roe"]);
+        assert_eq!(source.series_genres(&document), vec!["Action", "Comedy"]);
+    }
+
+    #[test]
+    fn test_madara_source_parses_series_metadata() {
+        let html = r#"
+            <div class="summary_image"><img src="placeholder.gif" data-src="https://cdn.example.com/cover.jpg"></div>
+            <div class="post-content_item">
+                <div class="summary-heading"><h5>Rating</h5></div>
+                <div class="summary-content">Average 4.3 / 5 out of 120</div>
+            </div>
+            <div class="post-content_item">
+                <div class="summary-heading"><h5>Alternative</h5></div>
+                <div class="summary-content"> Exemple, Beispiel; 例 </div>
+            </div>
+            <div class="post-content_item">
+                <div class="summary-heading"><h5>Status</h5></div>
+                <div class="summary-content">OnGoing</div>
+            </div>
+            <div class="post-content_item">
+                <div class="summary-heading"><h5>Release</h5></div>
+                <div class="summary-content"><a href="/y/2019">2019</a></div>
+            </div>
+            <div class="artist-content"><a href="/a/3">Jim Poe</a></div>
+            <div class="tags-content"><a href="/t/1">Magic</a></div>
+            <div class="summary__content"><p>A story
+                about   things.</p><p>More.</p></div>
+        "#;
+        let document = scraper::Html::parse_document(html);
+        let metadata = MadaraSource::mangaread().series_metadata(&document);
+
+        assert_eq!(metadata.alternative_titles, vec!["Exemple", "Beispiel", "例"]);
+        assert_eq!(metadata.artists, vec!["Jim Poe"]);
+        assert_eq!(metadata.tags, vec!["Magic"]);
+        assert_eq!(metadata.status, Some(SeriesStatus::Ongoing));
+        assert_eq!(metadata.release_year, Some(2019));
+        assert_eq!(metadata.rating, Some(4.3));
+        assert_eq!(metadata.description.as_deref(), Some("A story about things. More."));
+        assert_eq!(metadata.cover_url.as_deref(), Some("https://cdn.example.com/cover.jpg"));
+    }
+
+    #[test]
+    fn test_profile_overrides_summary_selectors() {
+        let html = r#"
+            <ul class="info">
+                <li><b>Status</b> <span>Completed</span></li>
+                <li><b>Release</b> <span>2015</span></li>
+            </ul>
+        "#;
+        let document = scraper::Html::parse_document(html);
+        let source = MadaraSource::from_profile(SourceProfile {
+            summary_item_selector: String::from(".info li"),
+            summary_heading_selector: String::from("b"),
+            summary_content_selector: String::from("span"),
+            ..SourceProfile::default()
+        }).unwrap();
+
+        let metadata = source.series_metadata(&document);
+        assert_eq!(metadata.status, Some(SeriesStatus::Completed));
+        assert_eq!(metadata.release_year, Some(2015));
+    }
+
+    #[test]
+    fn test_cover_attributes_come_from_the_profile() {
+        let html = r#"<div class="summary_image"><img src="placeholder.gif" data-cfsrc="https://cdn.example.com/cover.png" data-original="https://cdn.example.com/cover.jpg"></div>"#;
+        let document = scraper::Html::parse_document(html);
+
+        // Cloudflare keeps the real URL in data-cfsrc, which the default profile tries before src
+        let metadata = MadaraSource::mangaread().series_metadata(&document);
+        assert_eq!(metadata.cover_url.as_deref(), Some("https://cdn.example.com/cover.png"));
+
+        let source = MadaraSource::from_profile(SourceProfile {
+            cover_attributes: vec![String::from("data-original")],
+            ..SourceProfile::default()
+        }).unwrap();
+        assert_eq!(source.series_metadata(&document).cover_url.as_deref(), Some("https://cdn.example.com/cover.jpg"));
+    }
+
+    #[test]
+    fn test_profile_overrides_selectors() {
+        let html = r#"
+            <h2 class="reader-title">Chapter 7</h2>
+            <div class="reader"><img src="placeholder.gif" data-src="https://cdn.example.com/7.webp"></div>
+            <div class="reader"><img data-lazy-src="https://cdn.example.com/8.webp"></div>
+        "#;
+        let document = scraper::Html::parse_document(html);
+        let source = MadaraSource::from_profile(SourceProfile {
+            chapter_title_selector: String::from(".reader-title"),
+            image_selector: String::from(".reader img"),
+            image_attributes: vec![String::from("data-src"), String::from("data-lazy-src"), String::from("src")],
+            ..SourceProfile::default()
+        }).unwrap();
+
+        assert_eq!(source.chapter_title(&document).unwrap(), "Chapter 7");
+        assert_eq!(
+            source.page_images(&document).unwrap(),
+            vec!["https://cdn.example.com/7.webp", "https://cdn.example.com/8.webp"]
+        );
+    }
+
+    #[test]
+    fn test_madara_chapter_list_requests() {
+        let html = r#"<div id="manga-chapters-holder" data-id="4242"></div>"#;
+        let document = scraper::Html::parse_document(html);
+        let source = MadaraSource::mangaread();
+
+        let requests = source.chapter_list_requests("https://www.mangaread.org/manga/example/", &document);
+        assert_eq!(requests, vec![
+            ChapterListRequest {
+                url: String::from("https://www.mangaread.org/manga/example/ajax/chapters/"),
+                form: Vec::new(),
+            },
+            ChapterListRequest {
+                url: String::from("https://www.mangaread.org/wp-admin/admin-ajax.php"),
+                form: vec![
+                    (String::from("action"), String::from("manga_get_chapters")),
+                    (String::from("manga"), String::from("4242")),
+                ],
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_profile_selector_is_rejected() {
+        let profile = SourceProfile {
+            image_selector: String::from("img[["),
+            ..SourceProfile::default()
+        };
+
+        assert!(matches!(MadaraSource::from_profile(profile), Err(DownloadError::SelectorError(_))));
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::DownloadError;
+
+/// CSS selectors and attributes describing how to scrape a Madara-style site
+///
+/// Profiles can be loaded from TOML or JSON files, so sites with slightly different
+/// markup can be supported without recompiling. Missing selectors fall back to the
+/// values used for mangaread.org; a profile without `hosts` applies to any link.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceProfile {
+    /// Name of the profile, used in logs; profiles loaded from a file without one are named after it
+    pub name: String,
+    /// Hosts the profile applies to; an empty list applies it to any link
+    pub hosts: Vec<String>,
+    /// Selector for the series title on the series page
+    pub title_selector: String,
+    /// Selector for the author names on the series page
+    pub author_selector: String,
+    /// Selector for the genre names on the series page
+    pub genre_selector: String,
+    /// Selector for the artist names on the series page
+    pub artist_selector: String,
+    /// Selector for the tags on the series page
+    pub tag_selector: String,
+    /// Selector for the description on the series page
+    pub description_selector: String,
+    /// Selector for the cover image on the series page
+    pub cover_selector: String,
+    /// Cover image attributes holding the cover URL, tried in order
+    pub cover_attributes: Vec<String>,
+    /// Selector for the items of the summary table (status, release, rating...) on the series page
+    pub summary_item_selector: String,
+    /// Selector for the heading of a summary item, e.g. "Status"
+    pub summary_heading_selector: String,
+    /// Selector for the content of a summary item, e.g. "OnGoing"
+    pub summary_content_selector: String,
+    /// Selector for the chapter links on the series page
+    pub chapter_list_selector: String,
+    /// Selector for the chapter title on the chapter page
+    pub chapter_title_selector: String,
+    /// Selector for the page images on the chapter page
+    pub image_selector: String,
+    /// Image attributes holding the page URL, tried in order (e.g. lazy-load `data-src`)
+    pub image_attributes: Vec<String>,
+}
+
+impl Default for SourceProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            hosts: Vec::new(),
+            title_selector: String::from(".post-title h1"),
+            author_selector: String::from(".author-content a"),
+            genre_selector: String::from(".genres-content a"),
+            artist_selector: String::from(".artist-content a"),
+            tag_selector: String::from(".tags-content a"),
+            description_selector: String::from(".summary__content"),
+            cover_selector: String::from(".summary_image img"),
+            cover_attributes: ["data-src", "data-lazy-src", "data-cfsrc", "src"].map(String::from).to_vec(),
+            summary_item_selector: String::from(".post-content_item"),
+            summary_heading_selector: String::from(".summary-heading"),
+            summary_content_selector: String::from(".summary-content"),
+            chapter_list_selector: String::from(".wp-manga-chapter a"),
+            chapter_title_selector: String::from("#chapter-heading"),
+            image_selector: String::from(".page-break img"),
+            image_attributes: vec![String::from("src"), String::from("data-cfsrc")],
+        }
+    }
+}
+
+impl SourceProfile {
+    /// The built-in profile for https://www.mangaread.org
+    pub fn mangaread() -> Self {
+        Self {
+            name: String::from("mangaread.org"),
+            hosts: vec![String::from("mangaread.org")],
+            ..Self::default()
+        }
+    }
+
+    /// Load a profile from a `.toml` or `.json` file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, DownloadError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        let mut profile = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&contents)?,
+            Some("json") => Self::from_json(&contents)?,
+            _ => return Err(DownloadError::ParsingError(format!(
+                "Unsupported source profile format (expected .toml or .json): {}",
+                path.display()
+            ))),
+        };
+        if profile.name.is_empty() {
+            profile.name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        }
+        Ok(profile)
+    }
+
+    /// Parse a profile from TOML
+    pub fn from_toml(contents: &str) -> Result<Self, DownloadError> {
+        toml::from_str(contents)
+            .map_err(|e| DownloadError::ParsingError(format!("Failed to parse source profile: {}", e)))
+    }
+
+    /// Parse a profile from JSON
+    pub fn from_json(contents: &str) -> Result<Self, DownloadError> {
+        serde_json::from_str(contents)
+            .map_err(|e| DownloadError::ParsingError(format!("Failed to parse source profile: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_toml_profile_uses_defaults() {
+        let profile = SourceProfile::from_toml(r#"
+            name = "lazy-site"
+            hosts = ["lazy.example.com"]
+            image_attributes = ["data-src", "data-lazy-src", "src"]
+        "#).unwrap();
+
+        assert_eq!(profile.name, "lazy-site");
+        assert_eq!(profile.hosts, vec!["lazy.example.com"]);
+        assert_eq!(profile.image_attributes, vec!["data-src", "data-lazy-src", "src"]);
+        assert_eq!(profile.image_selector, SourceProfile::default().image_selector);
+    }
+
+    #[test]
+    fn test_profile_without_hosts_applies_to_any_link() {
+        let path = std::env::temp_dir().join("manga_downloader_profile_test_site.toml");
+        fs::write(&path, "image_selector = \".reader img\"\n").unwrap();
+
+        let profile = SourceProfile::from_file(&path).unwrap();
+        assert!(profile.hosts.is_empty());
+        assert_eq!(profile.name, "manga_downloader_profile_test_site");
+        assert_eq!(profile.image_selector, ".reader img");
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_json_profile() {
+        let profile = SourceProfile::from_json(r#"{
+            "title_selector": ".manga-title",
+            "chapter_list_selector": "ul.chapters a"
+        }"#).unwrap();
+
+        assert_eq!(profile.title_selector, ".manga-title");
+        assert_eq!(profile.chapter_list_selector, "ul.chapters a");
+        assert_eq!(profile.chapter_title_selector, "#chapter-heading");
+    }
+
+    #[test]
+    fn test_unknown_extension_is_rejected() {
+        let path = std::env::temp_dir().join("manga_downloader_profile_test.yaml");
+        fs::write(&path, "name: test").unwrap();
+
+        assert!(SourceProfile::from_file(&path).is_err());
+
+        let _ = fs::remove_file(path);
+    }
+}
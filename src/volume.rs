@@ -0,0 +1,159 @@
+use std::str::FromStr;
+
+use crate::error::DownloadError;
+use crate::manga_to_download::ChapterInfo;
+
+/// How downloaded chapters are combined into output files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeMode {
+    /// One file per chapter
+    #[default]
+    None,
+    /// One file per volume, taken from chapter titles such as "Vol.3 Chapter 12"
+    Volume,
+    /// One file per this many chapters
+    Every(usize),
+    /// One file for all chapters
+    All,
+}
+
+impl FromStr for MergeMode {
+    type Err = DownloadError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "none" => Ok(MergeMode::None),
+            "volume" => Ok(MergeMode::Volume),
+            "all" => Ok(MergeMode::All),
+            other => match other.parse::<usize>() {
+                Ok(count) if count > 0 => Ok(MergeMode::Every(count)),
+                _ => Err(DownloadError::ParsingError(format!(
+                    "Invalid merge mode '{}', expected none, volume, all or a number of chapters",
+                    value
+                ))),
+            },
+        }
+    }
+}
+
+/// Chapters that go into the same output file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChapterGroup {
+    /// Label of the group, e.g. "Volume 3" or "Chapters 1-10"
+    pub label: String,
+    /// Positions of the chapters in the list given to [`group_chapters`], in reading order
+    pub chapters: Vec<usize>,
+}
+
+/// Split chapters (in reading order) into the groups that become one output file each
+///
+/// Volumes and chapter numbers come from [`ChapterInfo::number`]. With [`MergeMode::None`]
+/// every chapter is its own group, labelled with its title.
+pub fn group_chapters(chapters: &[ChapterInfo], mode: MergeMode) -> Vec<ChapterGroup> {
+    let positions = (0..chapters.len()).collect::<Vec<_>>();
+
+    match mode {
+        MergeMode::None => positions.into_iter()
+            .map(|i| ChapterGroup { label: chapters[i].title.clone(), chapters: vec![i] })
+            .collect(),
+        MergeMode::All => vec![range_group(chapters, positions)],
+        MergeMode::Every(count) => positions.chunks(count)
+            .map(|chunk| range_group(chapters, chunk.to_vec()))
+            .collect(),
+        MergeMode::Volume => {
+            // Consecutive chapters of the same volume form a group; chapters without a
+            // volume number are grouped with the other unnumbered chapters next to them
+            let mut groups: Vec<(Option<u32>, Vec<usize>)> = Vec::new();
+            for i in positions {
+                let volume = chapters[i].number.volume;
+                match groups.last_mut() {
+                    Some((last, group)) if *last == volume => group.push(i),
+                    _ => groups.push((volume, vec![i])),
+                }
+            }
+
+            groups.into_iter()
+                .map(|(volume, group)| match volume {
+                    Some(volume) => ChapterGroup { label: format!("Volume {}", volume), chapters: group },
+                    None => range_group(chapters, group),
+                })
+                .collect()
+        }
+    }
+}
+
+/// Label a group by its first and last chapter numbers, or by their titles when either
+/// end has no number, so one label never mixes the two
+fn range_group(chapters: &[ChapterInfo], group: Vec<usize>) -> ChapterGroup {
+    let first = &chapters[group[0]];
+    let last = &chapters[group[group.len() - 1]];
+
+    let label = match (first.number.chapter, last.number.chapter) {
+        (Some(number), _) if group.len() == 1 => format!("Chapter {}", number),
+        _ if group.len() == 1 => first.title.clone(),
+        (Some(first), Some(last)) => format!("Chapters {}-{}", first, last),
+        _ => format!("{} to {}", first.title, last.title),
+    };
+    ChapterGroup { label, chapters: group }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapters(titles: &[&str]) -> Vec<ChapterInfo> {
+        titles.iter().enumerate()
+            .map(|(index, title)| ChapterInfo::new(index, title.to_string(), format!("https://example.com/manga/example/{}/", index)))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_merge_mode() {
+        assert_eq!("volume".parse::<MergeMode>().unwrap(), MergeMode::Volume);
+        assert_eq!("10".parse::<MergeMode>().unwrap(), MergeMode::Every(10));
+        assert_eq!("all".parse::<MergeMode>().unwrap(), MergeMode::All);
+        assert!("0".parse::<MergeMode>().is_err());
+        assert!("weekly".parse::<MergeMode>().is_err());
+    }
+
+    #[test]
+    fn test_group_by_volume() {
+        let chapters = chapters(&["Vol.1 Chapter 1", "Vol.1 Chapter 2", "Vol.2 Chapter 3", "Chapter 4", "Chapter 5"]);
+        let groups = group_chapters(&chapters, MergeMode::Volume);
+
+        assert_eq!(groups, vec![
+            ChapterGroup { label: String::from("Volume 1"), chapters: vec![0, 1] },
+            ChapterGroup { label: String::from("Volume 2"), chapters: vec![2] },
+            ChapterGroup { label: String::from("Chapters 4-5"), chapters: vec![3, 4] },
+        ]);
+    }
+
+    #[test]
+    fn test_group_every_n_chapters() {
+        let chapters = chapters(&["Chapter 1", "Chapter 2", "Chapter 3", "Chapter 4", "Chapter 5"]);
+        let groups = group_chapters(&chapters, MergeMode::Every(2));
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].label, "Chapters 1-2");
+        assert_eq!(groups[2], ChapterGroup { label: String::from("Chapter 5"), chapters: vec![4] });
+    }
+
+    #[test]
+    fn test_volume_prefix_must_start_a_word() {
+        // "Evolution" contains "vol" but is no volume
+        let chapters = chapters(&["Evolution 3", "Chapter 4.5"]);
+        let groups = group_chapters(&chapters, MergeMode::Volume);
+
+        assert_eq!(groups, vec![ChapterGroup { label: String::from("Evolution 3 to Chapter 4.5"), chapters: vec![0, 1] }]);
+    }
+
+    #[test]
+    fn test_unnumbered_chapters_are_labelled_by_title() {
+        let chapters = chapters(&["Prologue", "Chapter 1", "Chapter 2", "Epilogue"]);
+        let groups = group_chapters(&chapters, MergeMode::Every(2));
+
+        assert_eq!(groups[0].label, "Prologue to Chapter 1");
+        assert_eq!(groups[1].label, "Chapter 2 to Epilogue");
+        assert_eq!(group_chapters(&chapters[..1], MergeMode::All)[0].label, "Prologue");
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{ImageFormat, RgbImage};
+use log::{debug, trace};
+
+use crate::error::DownloadError;
+
+/// Default height in pixels of re-sliced webtoon pages
+pub const DEFAULT_PAGE_HEIGHT: u32 = 1800;
+
+/// Luma difference still considered part of a plain gutter row
+const GUTTER_TOLERANCE: u8 = 12;
+
+/// Consecutive plain rows needed before they count as a gutter between panels
+const MIN_GUTTER_ROWS: usize = 4;
+
+/// Stitches the slices of a webtoon chapter into one strip and cuts it into pages of about
+/// `page_height` pixels, on a blocking task so it doesn't stall the tokio runtime
+///
+/// See [`reslice_strip`].
+pub async fn reslice_chapter(image_paths: Vec<PathBuf>, output_dir: &Path, page_height: u32, quality: u8) -> Result<Vec<PathBuf>, DownloadError> {
+    let output_dir = output_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || reslice_strip(&image_paths, &output_dir, page_height, quality))
+        .await
+        .map_err(|e| DownloadError::ImageProcessingError(format!("Webtoon slicing task failed: {}", e)))?
+}
+
+/// Stitches `image_paths` vertically and cuts the strip into pages of about `page_height` pixels
+///
+/// Cuts are moved to the nearest plain gutter between panels, anywhere from half to
+/// one and a quarter of the page height, so panels aren't split across pages. Slices are
+/// scaled to the width of the first one. Only the part of the strip that hasn't become a
+/// page yet is kept in memory. Pages are saved as `page_NNNN.jpg` at `quality` in
+/// `output_dir` when every slice is a JPEG, and as `page_NNNN.png` otherwise.
+pub fn reslice_strip(image_paths: &[PathBuf], output_dir: &Path, page_height: u32, quality: u8) -> Result<Vec<PathBuf>, DownloadError> {
+    if image_paths.is_empty() {
+        return Err(DownloadError::ImageProcessingError(String::from("Cannot slice webtoon: no images provided")));
+    }
+    if page_height == 0 {
+        return Err(DownloadError::ImageProcessingError(String::from("Webtoon page height must be greater than 0")));
+    }
+
+    debug!("Re-slicing {} webtoon images into pages of {} pixels", image_paths.len(), page_height);
+    std::fs::create_dir_all(output_dir)?;
+
+    // JPEG strips stay JPEG, so pages stay small and the PDF writer can embed them as they are
+    let jpeg_quality = image_paths.iter()
+        .all(|path| image_format(path) == Some(ImageFormat::Jpeg))
+        .then_some(quality.clamp(1, 100));
+
+    let page_height = page_height as usize;
+    let max_height = page_height + page_height / 4;
+    let mut strip = Strip::default();
+    let mut pages = Vec::new();
+
+    for path in image_paths {
+        let slice = image::open(path)
+            .map_err(|e| DownloadError::ImageProcessingError(format!("Failed to load image {:?}: {}", path, e)))?;
+        let width = *strip.width.get_or_insert(slice.width());
+        let slice = if slice.width() == width {
+            slice.to_rgb8()
+        } else {
+            let height = (u64::from(slice.height()) * u64::from(width) / u64::from(slice.width())).max(1) as u32;
+            slice.resize_exact(width, height, FilterType::Lanczos3).to_rgb8()
+        };
+        strip.pixels.extend_from_slice(slice.as_raw());
+
+        while strip.height() > max_height {
+            let cut = strip.find_cut(page_height / 2, page_height, max_height);
+            pages.push(strip.save_page(cut, output_dir, pages.len(), jpeg_quality)?);
+        }
+    }
+
+    if strip.height() > 0 {
+        let height = strip.height();
+        pages.push(strip.save_page(height, output_dir, pages.len(), jpeg_quality)?);
+    }
+
+    debug!("Webtoon re-sliced into {} pages", pages.len());
+    Ok(pages)
+}
+
+/// The bottom part of the stitched strip that hasn't been cut into pages yet
+#[derive(Debug, Default)]
+struct Strip {
+    width: Option<u32>,
+    /// RGB rows, top to bottom
+    pixels: Vec<u8>,
+}
+
+impl Strip {
+    fn row_len(&self) -> usize {
+        self.width.unwrap_or(0) as usize * 3
+    }
+
+    fn height(&self) -> usize {
+        match self.row_len() {
+            0 => 0,
+            row_len => self.pixels.len() / row_len,
+        }
+    }
+
+    /// Whether every pixel of row `y` is about the same brightness
+    fn is_plain_row(&self, y: usize) -> bool {
+        let row_len = self.row_len();
+        let row = &self.pixels[y * row_len..(y + 1) * row_len];
+        let luma = row.chunks_exact(3)
+            .map(|pixel| ((u32::from(pixel[0]) * 299 + u32::from(pixel[1]) * 587 + u32::from(pixel[2]) * 114) / 1000) as u8);
+
+        let (min, max) = luma.fold((u8::MAX, u8::MIN), |(min, max), value| (min.min(value), max.max(value)));
+        max - min <= GUTTER_TOLERANCE
+    }
+
+    /// Row to cut at: the middle of the gutter closest to `target` between `min` and `max`,
+    /// or `target` itself when there is no gutter
+    fn find_cut(&self, min: usize, target: usize, max: usize) -> usize {
+        let max = max.min(self.height());
+        let mut best: Option<usize> = None;
+        let mut run_start = None;
+
+        for y in min..=max {
+            if y < max && self.is_plain_row(y) {
+                run_start.get_or_insert(y);
+                continue;
+            }
+            if let Some(start) = run_start.take()
+                && y - start >= MIN_GUTTER_ROWS
+            {
+                let middle = (start + y) / 2;
+                if best.is_none_or(|best| middle.abs_diff(target) < best.abs_diff(target)) {
+                    best = Some(middle);
+                }
+            }
+        }
+
+        match best {
+            Some(cut) => cut,
+            None => {
+                trace!("No gutter between rows {} and {}, cutting at {}", min, max, target);
+                target
+            }
+        }
+    }
+
+    /// Remove the first `height` rows from the strip and save them as page `index`, as JPEG
+    /// when `jpeg_quality` is given and as PNG otherwise
+    fn save_page(&mut self, height: usize, output_dir: &Path, index: usize, jpeg_quality: Option<u8>) -> Result<PathBuf, DownloadError> {
+        let width = self.width.unwrap_or(0);
+        let pixels = self.pixels.drain(..height * self.row_len()).collect::<Vec<_>>();
+        let page = RgbImage::from_raw(width, height as u32, pixels)
+            .ok_or_else(|| DownloadError::ImageProcessingError(String::from("Invalid webtoon page buffer")))?;
+
+        let extension = if jpeg_quality.is_some() { "jpg" } else { "png" };
+        let path = output_dir.join(format!("page_{:04}.{}", index, extension));
+        trace!("Saving {}x{} webtoon page to {:?}", width, height, path);
+        let result = match jpeg_quality {
+            Some(quality) => page.write_with_encoder(JpegEncoder::new_with_quality(BufWriter::new(File::create(&path)?), quality)),
+            None => page.save(&path),
+        };
+        result.map_err(|e| DownloadError::ImageProcessingError(format!("Failed to save webtoon page {:?}: {}", path, e)))?;
+
+        Ok(path)
+    }
+}
+
+/// Format of an image, detected from its content
+fn image_format(path: &Path) -> Option<ImageFormat> {
+    image::ImageReader::open(path).ok()?
+        .with_guessed_format().ok()?
+        .format()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    const PANEL: Rgb<u8> = Rgb([200, 30, 30]);
+    const GUTTER: Rgb<u8> = Rgb([255, 255, 255]);
+
+    /// Noisy panel rows where `is_panel` holds, white gutter rows elsewhere
+    fn panels(width: u32, height: u32, is_panel: impl Fn(u32) -> bool) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            match (is_panel(y), x % 2) {
+                (true, 0) => PANEL,
+                (true, _) => Rgb([20, 20, 20]),
+                (false, _) => GUTTER,
+            }
+        })
+    }
+
+    #[test]
+    fn test_reslice_cuts_at_gutters() {
+        let dir = std::env::temp_dir().join("manga_downloader_webtoon_test");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Panels of 300 rows separated by 40 white rows, in slices that cut through panels
+        let strip = panels(60, 3400, |y| y % 340 < 300);
+        let mut paths = Vec::new();
+        for (i, start) in (0..3400).step_by(500).enumerate() {
+            let height = 500.min(3400 - start);
+            let path = dir.join(format!("image_{:04}.png", i));
+            image::imageops::crop_imm(&strip, 0, start, 60, height).to_image().save(&path).unwrap();
+            paths.push(path);
+        }
+
+        let pages = reslice_strip(&paths, &dir.join("pages"), 1000, 85).unwrap();
+        let pages = pages.iter().map(|path| image::open(path).unwrap().to_rgb8()).collect::<Vec<_>>();
+
+        assert_eq!(pages.iter().map(|page| page.height()).sum::<u32>(), 3400);
+        for page in &pages[..pages.len() - 1] {
+            assert_eq!(page.width(), 60);
+            assert!(page.height() >= 500 && page.height() <= 1250);
+            // Every cut falls in a gutter, so no page starts or ends inside a panel
+            assert_eq!(*page.get_pixel(0, page.height() - 1), GUTTER);
+        }
+        for page in &pages[1..] {
+            assert_eq!(*page.get_pixel(0, 0), GUTTER);
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_reslice_scales_slices_to_first_width() {
+        let dir = std::env::temp_dir().join("manga_downloader_webtoon_width_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("a.png"), dir.join("b.png")];
+        panels(100, 200, |_| true).save(&paths[0]).unwrap();
+        panels(200, 400, |_| true).save(&paths[1]).unwrap();
+
+        let pages = reslice_strip(&paths, &dir.join("pages"), 1000, 85).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(image::image_dimensions(&pages[0]).unwrap(), (100, 400));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_find_cut_without_gutter_uses_target() {
+        let image = panels(10, 300, |_| true);
+        let strip = Strip { width: Some(10), pixels: image.into_raw() };
+        assert_eq!(strip.find_cut(50, 100, 125), 100);
+    }
+
+    #[test]
+    fn test_jpeg_slices_give_jpeg_pages() {
+        let dir = std::env::temp_dir().join("manga_downloader_webtoon_jpeg_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("a.jpg"), dir.join("b.jpg")];
+        for path in &paths {
+            panels(100, 600, |y| y % 300 < 250).save(path).unwrap();
+        }
+
+        let pages = reslice_strip(&paths, &dir.join("pages"), 500, 80).unwrap();
+        assert!(pages.len() > 1);
+        for page in &pages {
+            assert_eq!(page.extension().unwrap(), "jpg");
+            assert_eq!(image_format(page), Some(ImageFormat::Jpeg));
+        }
+
+        // One PNG slice is enough to keep the pages lossless
+        panels(100, 600, |_| true).save(dir.join("c.png")).unwrap();
+        let pages = reslice_strip(&[paths[0].clone(), dir.join("c.png")], &dir.join("mixed"), 500, 80).unwrap();
+        assert_eq!(image_format(&pages[0]), Some(ImageFormat::Png));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
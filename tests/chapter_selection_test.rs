@@ -1,4 +1,3 @@
-use download_manga::chapter_to_download::ChapterToDownload;
 use download_manga::manga_to_download::ChapterInfo;
 use download_manga::error::DownloadError;
 
@@ -63,21 +62,9 @@ fn parse_chapter_selection(chapters: &[ChapterInfo], input: &str) -> Result<Vec<
 fn test_parse_chapter_selection_individual() {
     // Create a list of test chapters
     let chapters = vec![
-        ChapterInfo {
-            index: 0,
-            title: "Chapter 1".to_string(),
-            url: "https://example.com/chapter-1".to_string(),
-        },
-        ChapterInfo {
-            index: 1,
-            title: "Chapter 2".to_string(),
-            url: "https://example.com/chapter-2".to_string(),
-        },
-        ChapterInfo {
-            index: 2,
-            title: "Chapter 3".to_string(),
-            url: "https://example.com/chapter-3".to_string(),
-        },
+        ChapterInfo::new(0, "Chapter 1".to_string(), "https://example.com/chapter-1".to_string()),
+        ChapterInfo::new(1, "Chapter 2".to_string(), "https://example.com/chapter-2".to_string()),
+        ChapterInfo::new(2, "Chapter 3".to_string(), "https://example.com/chapter-3".to_string()),
     ];
 
     // Test individual selection
@@ -91,26 +78,10 @@ fn test_parse_chapter_selection_individual() {
 fn test_parse_chapter_selection_range() {
     // Create a list of test chapters
     let chapters = vec![
-        ChapterInfo {
-            index: 0,
-            title: "Chapter 1".to_string(),
-            url: "https://example.com/chapter-1".to_string(),
-        },
-        ChapterInfo {
-            index: 1,
-            title: "Chapter 2".to_string(),
-            url: "https://example.com/chapter-2".to_string(),
-        },
-        ChapterInfo {
-            index: 2,
-            title: "Chapter 3".to_string(),
-            url: "https://example.com/chapter-3".to_string(),
-        },
-        ChapterInfo {
-            index: 3,
-            title: "Chapter 4".to_string(),
-            url: "https://example.com/chapter-4".to_string(),
-        },
+        ChapterInfo::new(0, "Chapter 1".to_string(), "https://example.com/chapter-1".to_string()),
+        ChapterInfo::new(1, "Chapter 2".to_string(), "https://example.com/chapter-2".to_string()),
+        ChapterInfo::new(2, "Chapter 3".to_string(), "https://example.com/chapter-3".to_string()),
+        ChapterInfo::new(3, "Chapter 4".to_string(), "https://example.com/chapter-4".to_string()),
     ];
 
     // Test range selection
@@ -123,31 +94,11 @@ fn test_parse_chapter_selection_range() {
 fn test_parse_chapter_selection_mixed() {
     // Create a list of test chapters
     let chapters = vec![
-        ChapterInfo {
-            index: 0,
-            title: "Chapter 1".to_string(),
-            url: "https://example.com/chapter-1".to_string(),
-        },
-        ChapterInfo {
-            index: 1,
-            title: "Chapter 2".to_string(),
-            url: "https://example.com/chapter-2".to_string(),
-        },
-        ChapterInfo {
-            index: 2,
-            title: "Chapter 3".to_string(),
-            url: "https://example.com/chapter-3".to_string(),
-        },
-        ChapterInfo {
-            index: 3,
-            title: "Chapter 4".to_string(),
-            url: "https://example.com/chapter-4".to_string(),
-        },
-        ChapterInfo {
-            index: 4,
-            title: "Chapter 5".to_string(),
-            url: "https://example.com/chapter-5".to_string(),
-        },
+        ChapterInfo::new(0, "Chapter 1".to_string(), "https://example.com/chapter-1".to_string()),
+        ChapterInfo::new(1, "Chapter 2".to_string(), "https://example.com/chapter-2".to_string()),
+        ChapterInfo::new(2, "Chapter 3".to_string(), "https://example.com/chapter-3".to_string()),
+        ChapterInfo::new(3, "Chapter 4".to_string(), "https://example.com/chapter-4".to_string()),
+        ChapterInfo::new(4, "Chapter 5".to_string(), "https://example.com/chapter-5".to_string()),
     ];
 
     // Test mixed selection
@@ -160,16 +111,8 @@ fn test_parse_chapter_selection_mixed() {
 fn test_parse_chapter_selection_invalid() {
     // Create a list of test chapters
     let chapters = vec![
-        ChapterInfo {
-            index: 0,
-            title: "Chapter 1".to_string(),
-            url: "https://example.com/chapter-1".to_string(),
-        },
-        ChapterInfo {
-            index: 1,
-            title: "Chapter 2".to_string(),
-            url: "https://example.com/chapter-2".to_string(),
-        },
+        ChapterInfo::new(0, "Chapter 1".to_string(), "https://example.com/chapter-1".to_string()),
+        ChapterInfo::new(1, "Chapter 2".to_string(), "https://example.com/chapter-2".to_string()),
     ];
 
     // Test invalid selections
@@ -183,21 +126,9 @@ fn test_parse_chapter_selection_invalid() {
 fn test_parse_chapter_selection_all() {
     // Create a list of test chapters
     let chapters = vec![
-        ChapterInfo {
-            index: 0,
-            title: "Chapter 1".to_string(),
-            url: "https://example.com/chapter-1".to_string(),
-        },
-        ChapterInfo {
-            index: 1,
-            title: "Chapter 2".to_string(),
-            url: "https://example.com/chapter-2".to_string(),
-        },
-        ChapterInfo {
-            index: 2,
-            title: "Chapter 3".to_string(),
-            url: "https://example.com/chapter-3".to_string(),
-        },
+        ChapterInfo::new(0, "Chapter 1".to_string(), "https://example.com/chapter-1".to_string()),
+        ChapterInfo::new(1, "Chapter 2".to_string(), "https://example.com/chapter-2".to_string()),
+        ChapterInfo::new(2, "Chapter 3".to_string(), "https://example.com/chapter-3".to_string()),
     ];
 
     // Test "all" selection
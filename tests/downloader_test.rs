@@ -1,10 +1,20 @@
 use std::path::Path;
+use std::time::Duration;
 
 // Import the crate being tested
-use download_manga::downloader;
+use download_manga::downloader::{self, MissingPagePolicy};
+use download_manga::error::DownloadError;
+use download_manga::http::{HttpClient, HttpConfig};
+use download_manga::retry::RetryPolicy;
 
-// Note: Async tests were removed due to tokio runtime issues
-// These should be reimplemented in a future PR with proper tokio test setup
+/// A blank PNG image of the given size, as served by the mock image hosts
+fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image::RgbImage::new(width, height)
+        .write_to(&mut bytes, image::ImageFormat::Png)
+        .unwrap();
+    bytes.into_inner()
+}
 
 #[test]
 fn test_build_chapter_path() {
@@ -43,4 +53,231 @@ fn test_sanitize_filename() {
         // Most platforms don't allow at least slashes in filenames
         assert!(!sanitized.contains('/'));
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_image_extension_from_magic_bytes() {
+    assert_eq!(downloader::image_extension(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10]), Some("jpg"));
+    assert_eq!(downloader::image_extension(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("png"));
+    assert_eq!(downloader::image_extension(b"GIF89a\x01\0\x01\0"), Some("gif"));
+    assert_eq!(downloader::image_extension(b"RIFF\x24\0\0\0WEBPVP8 "), Some("webp"));
+    assert_eq!(downloader::image_extension(b"<html>blocked</html>"), None);
+}
+
+#[tokio::test]
+async fn test_download_images_returns_pages_in_order() {
+    let mut server = mockito::Server::new_async().await;
+    let mut mocks = Vec::new();
+    for i in 0..6 {
+        mocks.push(server.mock("GET", format!("/{}.png", i).as_str())
+            .with_body(png_bytes(i + 1, 1))
+            .create_async().await);
+    }
+
+    let client = HttpClient::new(&HttpConfig::default()).unwrap();
+    let chapter_dir = std::env::temp_dir().join("manga_downloader_order_test");
+    downloader::ensure_dir_exists(&chapter_dir).unwrap();
+
+    let urls = (0..6).map(|i| format!("{}/{}.png", server.url(), i)).collect::<Vec<_>>();
+    let report = downloader::download_images(&client, urls, None, &chapter_dir, 6).await;
+
+    assert!(report.is_complete());
+    for (i, page) in report.succeeded.iter().enumerate() {
+        assert_eq!(page.index, i);
+        assert_eq!(page.path, chapter_dir.join(format!("image_{:04}.png", i)));
+        assert_eq!(image::image_dimensions(&page.path).unwrap(), (i as u32 + 1, 1));
+    }
+
+    let _ = std::fs::remove_dir_all(chapter_dir);
+}
+
+#[tokio::test]
+async fn test_download_image_sends_configured_headers() {
+    let mut server = mockito::Server::new_async().await;
+    let image = server.mock("GET", "/image.jpg")
+        .match_header("referer", "https://example.com/manga/example/chapter-1/")
+        .match_header("user-agent", "test-agent")
+        .match_header("accept-language", "en")
+        .match_header("cookie", "session=abc")
+        .with_body(png_bytes(2, 2))
+        .create_async().await;
+
+    let config = HttpConfig {
+        user_agent: String::from("test-agent"),
+        headers: vec![(String::from("Accept-Language"), String::from("en"))],
+        ..HttpConfig::default()
+    };
+    let client = HttpClient::new(&config).unwrap();
+    client.add_cookie("session=abc", &server.url()).unwrap();
+
+    let path = std::env::temp_dir().join("manga_downloader_header_test.jpg");
+    let url = format!("{}/image.jpg", server.url());
+    let saved = downloader::download_image(&client, &url, Some("https://example.com/manga/example/chapter-1/"), &path, None)
+        .await
+        .unwrap();
+
+    image.assert_async().await;
+    // The extension follows the real format of the image
+    assert_eq!(saved, path.with_extension("png"));
+    assert_eq!(std::fs::read(&saved).unwrap(), png_bytes(2, 2));
+
+    let _ = std::fs::remove_file(saved);
+}
+
+#[tokio::test]
+async fn test_download_image_retries_transient_errors() {
+    let mut server = mockito::Server::new_async().await;
+    let unavailable = server.mock("GET", "/flaky.jpg")
+        .with_status(503)
+        .with_header("retry-after", "0")
+        .expect(2)
+        .create_async().await;
+    let image = server.mock("GET", "/flaky.jpg")
+        .with_body(png_bytes(2, 2))
+        .expect(1)
+        .create_async().await;
+
+    let config = HttpConfig {
+        retry: RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        },
+        ..HttpConfig::default()
+    };
+    let client = HttpClient::new(&config).unwrap();
+
+    let path = std::env::temp_dir().join("manga_downloader_retry_test.jpg");
+    let url = format!("{}/flaky.jpg", server.url());
+    let saved = downloader::download_image(&client, &url, None, &path, None).await.unwrap();
+
+    unavailable.assert_async().await;
+    image.assert_async().await;
+    assert_eq!(std::fs::read(&saved).unwrap(), png_bytes(2, 2));
+
+    let _ = std::fs::remove_file(saved);
+}
+
+#[tokio::test]
+async fn test_download_image_does_not_retry_client_errors() {
+    let mut server = mockito::Server::new_async().await;
+    let missing = server.mock("GET", "/missing.jpg")
+        .with_status(404)
+        .expect(1)
+        .create_async().await;
+
+    let client = HttpClient::new(&HttpConfig::default()).unwrap();
+
+    let path = std::env::temp_dir().join("manga_downloader_missing_test.jpg");
+    let url = format!("{}/missing.jpg", server.url());
+    let result = downloader::download_image(&client, &url, None, &path, None).await;
+
+    missing.assert_async().await;
+    assert!(matches!(result, Err(DownloadError::HttpStatus(404, _))));
+}
+
+#[tokio::test]
+async fn test_download_image_rejects_pages_that_are_not_images() {
+    let mut server = mockito::Server::new_async().await;
+    let _blocked = server.mock("GET", "/blocked.jpg")
+        .with_header("content-type", "text/html")
+        .with_body("<html><title>Just a moment...</title></html>")
+        .create_async().await;
+
+    let client = HttpClient::new(&HttpConfig::default()).unwrap();
+    let path = std::env::temp_dir().join("manga_downloader_not_image_test.jpg");
+    let url = format!("{}/blocked.jpg", server.url());
+    let result = downloader::download_image(&client, &url, None, &path, None).await;
+
+    assert!(matches!(result, Err(DownloadError::ImageProcessingError(_))));
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_download_images_reports_failed_pages() {
+    let mut server = mockito::Server::new_async().await;
+    let _first = server.mock("GET", "/1.png")
+        .with_body(png_bytes(30, 40))
+        .create_async().await;
+    let _second = server.mock("GET", "/2.png")
+        .with_status(404)
+        .create_async().await;
+    let _third = server.mock("GET", "/3.png")
+        .with_body(png_bytes(30, 40))
+        .create_async().await;
+
+    let client = HttpClient::new(&HttpConfig::default()).unwrap();
+    let chapter_dir = std::env::temp_dir().join("manga_downloader_report_test");
+    downloader::ensure_dir_exists(&chapter_dir).unwrap();
+
+    let urls = (1..=3).map(|i| format!("{}/{}.png", server.url(), i)).collect::<Vec<_>>();
+    let mut report = downloader::download_images(&client, urls.clone(), None, &chapter_dir, 2).await;
+
+    assert!(!report.is_complete());
+    assert_eq!(report.page_count(), 3);
+    assert_eq!(report.succeeded.len(), 2);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].index, 1);
+    assert_eq!(report.failed[0].url, urls[1]);
+    assert!(matches!(report.failed[0].error, DownloadError::HttpStatus(404, _)));
+    assert_eq!(report.summary(), "2 of 3 pages, pages 2 missing");
+
+    // Pages are in reading order and named after their real format
+    assert_eq!(report.succeeded[0].index, 0);
+    assert_eq!(report.succeeded[0].path, chapter_dir.join("image_0000.png"));
+    assert_eq!(report.succeeded[1].path, chapter_dir.join("image_0002.png"));
+
+    // fail: nothing is exported
+    let result = report.pages_for_export(MissingPagePolicy::Fail, &chapter_dir);
+    assert!(matches!(result, Err(DownloadError::MissingPages(_))));
+
+    // skip: the remaining pages, in order
+    let pages = report.pages_for_export(MissingPagePolicy::Skip, &chapter_dir).unwrap();
+    let page_path = |index: usize| report.succeeded.iter().find(|page| page.index == index).unwrap().path.clone();
+    assert_eq!(pages, vec![page_path(0), page_path(2)]);
+
+    // placeholder: a generated page of the same size takes the missing page's place
+    let pages = report.pages_for_export(MissingPagePolicy::Placeholder, &chapter_dir).unwrap();
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0], page_path(0));
+    assert_eq!(pages[2], page_path(2));
+    assert!(pages[1].file_name().unwrap().to_string_lossy().starts_with("missing_"));
+    assert_eq!(image::image_dimensions(&pages[1]).unwrap(), (30, 40));
+
+    // Spreads found while processing the pages show up in the summary
+    report.spreads = vec![0, 2];
+    assert_eq!(report.summary(), "2 of 3 pages, pages 2 missing, spreads on pages 1, 3");
+
+    let _ = std::fs::remove_dir_all(chapter_dir);
+}
+
+#[tokio::test]
+async fn test_download_remaining_images_skips_completed_pages() {
+    let mut server = mockito::Server::new_async().await;
+    let first = server.mock("GET", "/1.png")
+        .expect(0)
+        .create_async().await;
+    let second = server.mock("GET", "/2.png")
+        .with_body(png_bytes(30, 40))
+        .expect(1)
+        .create_async().await;
+
+    let client = HttpClient::new(&HttpConfig::default()).unwrap();
+    let chapter_dir = std::env::temp_dir().join("manga_downloader_resume_test");
+    downloader::ensure_dir_exists(&chapter_dir).unwrap();
+    let done = chapter_dir.join("image_0000.png");
+    std::fs::write(&done, png_bytes(30, 40)).unwrap();
+
+    let urls = (1..=2).map(|i| format!("{}/{}.png", server.url(), i)).collect::<Vec<_>>();
+    let completed = vec![downloader::DownloadedPage { index: 0, url: urls[0].clone(), path: done.clone() }];
+    let report = downloader::download_remaining_images(&client, urls, None, &chapter_dir, 2, completed).await;
+
+    first.assert_async().await;
+    second.assert_async().await;
+    assert!(report.is_complete());
+    assert_eq!(report.succeeded[0].path, done);
+    assert_eq!(report.succeeded[1].path, chapter_dir.join("image_0001.png"));
+    // Nothing is left under a temporary name
+    assert!(!downloader::partial_path(&report.succeeded[1].path).exists());
+
+    let _ = std::fs::remove_dir_all(chapter_dir);
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use download_manga::error::DownloadError;
+use download_manga::http::{HttpClient, HttpConfig};
+use download_manga::manga_to_download::MangaToDownload;
+use download_manga::source::MadaraSource;
+use mockito::Matcher;
+
+// Series page of a newer Madara site: the chapter list is loaded by JavaScript
+const SERIES_PAGE: &str = r#"
+<html><body>
+  <div class="post-title"><h1>Example Manga</h1></div>
+  <div id="manga-chapters-holder" data-id="4242"></div>
+</body></html>
+"#;
+
+const CHAPTER_FRAGMENT: &str = r#"
+<ul class="main version-chap">
+  <li class="wp-manga-chapter"><a href="https://example.com/manga/example/chapter-2/">Chapter 2</a></li>
+  <li class="wp-manga-chapter"><a href="https://example.com/manga/example/chapter-1/">Chapter 1</a></li>
+</ul>
+"#;
+
+fn client() -> HttpClient {
+    HttpClient::new(&HttpConfig::default()).unwrap()
+}
+
+#[tokio::test]
+async fn test_chapter_list_from_ajax_chapters_endpoint() {
+    let mut server = mockito::Server::new_async().await;
+    let _series = server.mock("GET", "/manga/example/")
+        .with_body(SERIES_PAGE)
+        .create_async().await;
+    let ajax = server.mock("POST", "/manga/example/ajax/chapters/")
+        .with_body(CHAPTER_FRAGMENT)
+        .create_async().await;
+
+    let link = format!("{}/manga/example/", server.url());
+    let manga = MangaToDownload::with_source(link, 1, Arc::new(MadaraSource::mangaread()), client()).await.unwrap();
+    let chapters = manga.list_available_chapters().unwrap();
+
+    ajax.assert_async().await;
+    assert_eq!(manga.title, "Example Manga");
+    assert_eq!(chapters.len(), 2);
+    assert_eq!(chapters[0].title, "Chapter 1");
+    assert_eq!(chapters[1].url, "https://example.com/manga/example/chapter-2/");
+}
+
+#[tokio::test]
+async fn test_chapter_list_falls_back_to_admin_ajax() {
+    let mut server = mockito::Server::new_async().await;
+    let _series = server.mock("GET", "/manga/example/")
+        .with_body(SERIES_PAGE)
+        .create_async().await;
+    let _chapters_endpoint = server.mock("POST", "/manga/example/ajax/chapters/")
+        .with_status(404)
+        .create_async().await;
+    let admin_ajax = server.mock("POST", "/wp-admin/admin-ajax.php")
+        .match_body(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("action".into(), "manga_get_chapters".into()),
+            Matcher::UrlEncoded("manga".into(), "4242".into()),
+        ]))
+        .with_body(CHAPTER_FRAGMENT)
+        .create_async().await;
+
+    let link = format!("{}/manga/example/", server.url());
+    let manga = MangaToDownload::with_source(link, 1, Arc::new(MadaraSource::mangaread()), client()).await.unwrap();
+    let chapters = manga.list_available_chapters().unwrap();
+
+    admin_ajax.assert_async().await;
+    assert_eq!(chapters.len(), 2);
+}
+
+#[tokio::test]
+async fn test_no_chapters_when_ajax_fallbacks_fail() {
+    let mut server = mockito::Server::new_async().await;
+    let _series = server.mock("GET", "/manga/example/")
+        .with_body(SERIES_PAGE)
+        .create_async().await;
+    let _chapters_endpoint = server.mock("POST", "/manga/example/ajax/chapters/")
+        .with_status(404)
+        .create_async().await;
+    let _admin_ajax = server.mock("POST", "/wp-admin/admin-ajax.php")
+        .with_body("0")
+        .create_async().await;
+
+    let link = format!("{}/manga/example/", server.url());
+    let manga = MangaToDownload::with_source(link, 1, Arc::new(MadaraSource::mangaread()), client()).await.unwrap();
+
+    assert!(matches!(manga.list_available_chapters(), Err(DownloadError::ElementNotFound(_))));
+}